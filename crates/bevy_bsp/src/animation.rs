@@ -0,0 +1,181 @@
+use std::collections::HashMap;
+
+use bevy::prelude::{Texture as BevyTexture, *};
+use decoder::format::gold_src_30::{SequenceKind, TextureSequence};
+use decoder::format::GoldSrc30Bsp;
+
+use crate::render::RenderModeMaterials;
+use crate::wad::{BspWad, WadManager};
+use crate::{BspRenderMode, BspTexture};
+
+/// Rate the engine plays texture animations at
+const ANIMATION_FPS: f64 = 10.0;
+
+/// Selects the alternate `+A` to `+J` texture sequence for the faces of a brush entity
+#[derive(Debug, Clone, Reflect, Default)]
+#[reflect(Component)]
+pub struct BspTextureFrame {
+    pub alternate: bool,
+}
+
+impl BspTextureFrame {
+    pub fn toggle(&mut self) {
+        self.alternate = !self.alternate;
+    }
+}
+
+/// Face using a `+0` to `+9` texture sequence. Frames embedded in the map have
+/// their material, frames from a WAD have a default handle and resolve by name
+#[derive(Debug, Clone, Reflect, Default)]
+#[reflect(Component)]
+pub struct BspAnimatedTexture {
+    frames: Vec<String>,
+    alternate_frames: Vec<String>,
+    materials: Vec<Handle<StandardMaterial>>,
+    alternate_materials: Vec<Handle<StandardMaterial>>,
+    wads: Vec<String>,
+}
+
+pub(crate) struct LoadedSequences {
+    sequences: Vec<TextureSequence>,
+    by_texture: HashMap<usize, usize>,
+    animations: Vec<BspAnimatedTexture>,
+}
+
+impl LoadedSequences {
+    pub(crate) fn new(
+        bsp: &GoldSrc30Bsp,
+        textures: &[BspTexture],
+        wads: &[String],
+    ) -> LoadedSequences {
+        let sequences = bsp.texture_sequences();
+        let mut by_texture = HashMap::new();
+        let mut animations = vec![];
+
+        let frame = |idx: &usize| {
+            let name = bsp.textures[*idx].name_string();
+            let material = textures
+                .iter()
+                .find(|t| t.idx == *idx)
+                .map(|t| t.material.clone())
+                .unwrap_or_default();
+
+            (name, material)
+        };
+
+        for (sequence_idx, sequence) in sequences.iter().enumerate() {
+            for idx in sequence
+                .frames
+                .iter()
+                .chain(sequence.alternate_frames.iter())
+            {
+                by_texture.insert(*idx, sequence_idx);
+            }
+
+            let (frames, materials) = sequence.frames.iter().map(frame).unzip();
+            let (alternate_frames, alternate_materials) =
+                sequence.alternate_frames.iter().map(frame).unzip();
+
+            animations.push(BspAnimatedTexture {
+                frames,
+                alternate_frames,
+                materials,
+                alternate_materials,
+                wads: wads.to_vec(),
+            });
+        }
+
+        LoadedSequences {
+            sequences,
+            by_texture,
+            animations,
+        }
+    }
+
+    /// Texture a face starts with, random tiles pick one of their frames per face
+    pub(crate) fn face_texture(&self, face_idx: usize, idx_miptex: usize) -> usize {
+        match self
+            .by_texture
+            .get(&idx_miptex)
+            .map(|idx| &self.sequences[*idx])
+        {
+            Some(sequence) if sequence.kind == SequenceKind::Random => {
+                let hash = face_idx.wrapping_mul(2_654_435_761) >> 8;
+
+                sequence.frames[hash % sequence.frames.len()]
+            }
+            _ => idx_miptex,
+        }
+    }
+
+    pub(crate) fn animation(&self, idx_miptex: usize) -> Option<BspAnimatedTexture> {
+        let idx = *self.by_texture.get(&idx_miptex)?;
+
+        if self.sequences[idx].kind == SequenceKind::Animated {
+            Some(self.animations[idx].clone())
+        } else {
+            None
+        }
+    }
+}
+
+pub(crate) fn animate_textures_system(
+    time: Res<Time>,
+    mut manager: ResMut<WadManager>,
+    wads: Res<Assets<BspWad>>,
+    mut textures: ResMut<Assets<BevyTexture>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut render_mode_materials: ResMut<RenderModeMaterials>,
+    texture_frames: Query<&BspTextureFrame>,
+    render_modes: Query<&BspRenderMode>,
+    mut faces: Query<(&BspAnimatedTexture, &Parent, &mut Handle<StandardMaterial>)>,
+) {
+    let tick = (time.seconds_since_startup() * ANIMATION_FPS) as usize;
+
+    for (animation, parent, mut material) in faces.iter_mut() {
+        let alternate = texture_frames
+            .get(parent.0)
+            .map(|frame| frame.alternate)
+            .unwrap_or_default();
+
+        let (frames, frame_materials) = if alternate && !animation.alternate_frames.is_empty() {
+            (&animation.alternate_frames, &animation.alternate_materials)
+        } else {
+            (&animation.frames, &animation.materials)
+        };
+
+        if frames.is_empty() {
+            continue;
+        }
+
+        let frame = tick % frames.len();
+
+        let handle = if frame_materials[frame] != Handle::default() {
+            Some(frame_materials[frame].clone())
+        } else {
+            manager
+                .material(
+                    &frames[frame],
+                    &animation.wads,
+                    &wads,
+                    &mut textures,
+                    &mut materials,
+                )
+                .map(|(_, handle)| handle)
+        };
+
+        // Faces of entities with a render mode get the frame's variant
+        let handle = match (handle, render_modes.get(parent.0)) {
+            (Some(handle), Ok(render_mode)) => {
+                render_mode_materials.variant(&mut materials, &handle, render_mode)
+            }
+            (handle, _) => handle,
+        };
+
+        if let Some(handle) = handle {
+            if *material != handle {
+                *material = handle;
+            }
+        }
+    }
+}
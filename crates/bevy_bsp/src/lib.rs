@@ -1,6 +1,5 @@
-use std::collections::{HashMap, HashSet};
-use std::fs;
-use std::io::{BufReader, Cursor};
+use std::collections::HashMap;
+use std::io::Cursor;
 
 use anyhow::format_err;
 use bevy::asset::{AssetLoader, LoadContext, LoadedAsset};
@@ -11,19 +10,155 @@ use bevy::render::pipeline::PrimitiveTopology;
 use bevy::render::texture::{AddressMode, Extent3d, SamplerDescriptor};
 use bevy::render::wireframe::Wireframe;
 use bevy::utils::BoxedFuture;
-use decoder::format::gold_src_30::Texture;
+use decoder::entity::Entity as DecodedEntity;
+use decoder::format::gold_src_30::{Model, Texture};
 use decoder::format::GoldSrc30Bsp;
-use decoder::{BspFormat, WadDecoder};
-
-#[derive(Debug, Clone, Default)]
+use decoder::tga::Image;
+use decoder::wad::declared_wads;
+
+use self::animation::LoadedSequences;
+use self::wad::WadManager;
+use decoder::BspFormat;
+
+pub use self::animation::{BspAnimatedTexture, BspTextureFrame};
+pub use self::mover::{BspMover, BspMoverKind, BspUseEvent};
+pub use self::render::{BspRenderMode, BspRenderModeKind};
+pub use self::sky::{BspSky, SKY_FAR_PLANE};
+pub use self::surface::BspSurfaceAnimation;
+pub use self::trigger::{BspTrigger, BspTriggerActivator, BspTriggerEvent};
+pub use self::vfs::{BspVfs, BspVfsPlugin, VfsAssetIo};
+pub use self::wad::{BspWad, WadFileLoader};
+
+mod animation;
+mod mover;
+mod render;
+mod sky;
+mod surface;
+mod trigger;
+mod vfs;
+mod wad;
+
+#[derive(Debug, Clone)]
 pub struct BspConfig {
     pub show_wireframe: bool,
+    /// Asset directories searched, in order, for the WADs a map declares
+    pub wad_dirs: Vec<String>,
+}
+
+impl Default for BspConfig {
+    fn default() -> Self {
+        BspConfig {
+            show_wireframe: false,
+            wad_dirs: vec!["wads".to_string()],
+        }
+    }
 }
 
 #[derive(Debug, Clone, Reflect, Default)]
 #[reflect(Component)]
 pub struct BspMesh;
 
+/// An entity from the map's entity lump
+#[derive(Debug, Clone, Reflect, Default)]
+#[reflect(Component)]
+pub struct BspEntity {
+    pub classname: String,
+    pub properties: bevy::utils::HashMap<String, String>,
+}
+
+impl BspEntity {
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.properties.get(key).map(String::as_str)
+    }
+}
+
+impl From<&DecodedEntity> for BspEntity {
+    fn from(entity: &DecodedEntity) -> Self {
+        BspEntity {
+            classname: entity.classname().unwrap_or_default().to_string(),
+            properties: entity.properties.iter().cloned().collect(),
+        }
+    }
+}
+
+/// Local bounds of the brush model an entity uses
+#[derive(Debug, Clone, Reflect, Default)]
+#[reflect(Component)]
+pub struct BspBrushModel {
+    pub mins: Vec3,
+    pub maxs: Vec3,
+}
+
+impl BspBrushModel {
+    /// Distance along the ray where it enters the bounds, using the slab method
+    pub fn ray_intersection(&self, origin: Vec3, direction: Vec3) -> Option<f32> {
+        let mut near = f32::NEG_INFINITY;
+        let mut far = f32::INFINITY;
+
+        for axis in 0..3 {
+            let (o, d) = (origin[axis], direction[axis]);
+            let (min, max) = (self.mins[axis], self.maxs[axis]);
+
+            if d.abs() < f32::EPSILON {
+                if o < min || o > max {
+                    return None;
+                }
+            } else {
+                let t0 = (min - o) / d;
+                let t1 = (max - o) / d;
+
+                near = near.max(t0.min(t1));
+                far = far.min(t0.max(t1));
+            }
+        }
+
+        if near <= far && far >= 0.0 {
+            Some(near.max(0.0))
+        } else {
+            None
+        }
+    }
+}
+
+pub type BspSpawnHook = Box<dyn Fn(&mut Commands, Entity, &BspEntity) + Send + Sync>;
+
+/// Hooks to run, per classname, for each entity of a spawned map instance
+#[derive(Default)]
+pub struct BspSpawnHooks {
+    hooks: HashMap<String, Vec<BspSpawnHook>>,
+}
+
+impl BspSpawnHooks {
+    pub fn add<F>(&mut self, classname: &str, hook: F)
+    where
+        F: Fn(&mut Commands, Entity, &BspEntity) + Send + Sync + 'static,
+    {
+        self.hooks
+            .entry(classname.to_string())
+            .or_default()
+            .push(Box::new(hook));
+    }
+}
+
+pub trait AddBspSpawnHook {
+    fn add_bsp_spawn_hook<F>(&mut self, classname: &str, hook: F) -> &mut Self
+    where
+        F: Fn(&mut Commands, Entity, &BspEntity) + Send + Sync + 'static;
+}
+
+impl AddBspSpawnHook for AppBuilder {
+    fn add_bsp_spawn_hook<F>(&mut self, classname: &str, hook: F) -> &mut Self
+    where
+        F: Fn(&mut Commands, Entity, &BspEntity) + Send + Sync + 'static,
+    {
+        self.world_mut()
+            .get_resource_or_insert_with(BspSpawnHooks::default)
+            .add(classname, hook);
+
+        self
+    }
+}
+
 /// Adds support for Bsp file rendering
 #[derive(Default)]
 pub struct BspPlugin;
@@ -31,14 +166,38 @@ pub struct BspPlugin;
 impl Plugin for BspPlugin {
     fn build(&self, app: &mut AppBuilder) {
         app.init_asset_loader::<BspFileLoader>()
+            .init_asset_loader::<WadFileLoader>()
             .init_resource::<BspConfig>()
+            .init_resource::<BspSpawnHooks>()
             .register_type::<BspMesh>()
+            .register_type::<BspEntity>()
+            .register_type::<BspBrushModel>()
+            .register_type::<BspSky>()
+            .register_type::<BspTextureFrame>()
+            .register_type::<BspAnimatedTexture>()
+            .register_type::<BspSurfaceAnimation>()
             .register_type::<BspNeedsWad>()
+            .register_type::<BspTrigger>()
+            .register_type::<BspTriggerActivator>()
+            .add_event::<BspTriggerEvent>()
+            .add_event::<BspUseEvent>()
             .add_asset::<BspFile>()
+            .add_asset::<BspWad>()
             .insert_resource(WadManager::default())
-            .add_startup_system(load_wads_system.system())
+            .init_resource::<render::RenderModeMaterials>()
             .add_system(add_wireframes_system.system())
-            .add_system(apply_wad_textures_system.system());
+            .add_system(wad::apply_wad_textures_system.system())
+            .add_system(wad::reload_wads_system.system())
+            .add_system(run_spawn_hooks_system.system())
+            .add_system(trigger::trigger_system.system())
+            .add_system(mover::setup_movers_system.system())
+            .add_system(mover::use_movers_system.system())
+            .add_system(mover::move_movers_system.system())
+            .add_system(render::setup_render_modes_system.system())
+            .add_system(render::apply_render_modes_system.system())
+            .add_system(sky::sky_follow_camera_system.system())
+            .add_system(animation::animate_textures_system.system())
+            .add_system(surface::animate_surfaces_system.system());
     }
 }
 
@@ -60,7 +219,29 @@ impl AssetLoader for BspFileLoader {
             let decoder = decoder::BspDecoder::from_reader(cursor)?;
             let format = decoder.decode_any()?;
 
-            load_format(format, load_context)?;
+            let BspFormat::GoldSrc30(gold_src) = &format;
+
+            for error in gold_src.entity_errors.iter() {
+                warn!(
+                    "Entity skipped: {}: {}",
+                    load_context.path().display(),
+                    error
+                );
+            }
+
+            let skyname = gold_src
+                .entities
+                .iter()
+                .find(|e| e.classname() == Some("worldspawn"))
+                .and_then(|e| e.get("skyname"))
+                .map(str::to_string);
+
+            let sky = match skyname {
+                Some(skyname) => sky::load_sky_images(&skyname, load_context).await,
+                None => None,
+            };
+
+            load_format(format, sky, load_context)?;
 
             Ok(())
         })
@@ -71,17 +252,31 @@ impl AssetLoader for BspFileLoader {
     }
 }
 
-fn load_format(format: BspFormat, load_context: &mut LoadContext) -> Result<(), anyhow::Error> {
+fn load_format(
+    format: BspFormat,
+    sky: Option<Vec<Image>>,
+    load_context: &mut LoadContext,
+) -> Result<(), anyhow::Error> {
     let BspFormat::GoldSrc30(gold_src) = format;
 
-    load_gold_src_format(gold_src, load_context)?;
+    load_gold_src_format(gold_src, sky, load_context)?;
 
     Ok(())
 }
 
 struct BspFace {
+    idx: usize,
     mesh: Handle<Mesh>,
     idx_miptex: Option<usize>,
+    surface: Option<BspSurfaceAnimation>,
+}
+
+struct LoadedEntity {
+    entity: BspEntity,
+    transform: Transform,
+    faces: Vec<BspFace>,
+    bounds: Option<BspBrushModel>,
+    trigger: Option<BspTrigger>,
 }
 
 struct BspDebugVolume {
@@ -101,10 +296,13 @@ struct BspTexture {
 #[reflect(Component)]
 struct BspNeedsWad {
     name: String,
+    /// WADs the map declares, in search order
+    wads: Vec<String>,
 }
 
 fn load_gold_src_format(
     bsp: GoldSrc30Bsp,
+    sky: Option<Vec<Image>>,
     load_context: &mut LoadContext,
 ) -> Result<(), anyhow::Error> {
     let model = bsp
@@ -134,9 +332,9 @@ fn load_gold_src_format(
     }
 
     let mut textures = vec![];
-    let mut faces = vec![];
     let mut debug_volumes = vec![];
     let mut wad_indexes = HashMap::new();
+    let wads = declared_wads(&bsp.entities);
 
     // Add textures
     for (idx, texture) in bsp.textures.iter().enumerate() {
@@ -168,10 +366,18 @@ fn load_gold_src_format(
             let name = String::from_utf8_lossy(&texture.name).to_string();
             let name = name.split('\0').next().unwrap_or_default().to_string();
 
-            wad_indexes.insert(idx, BspNeedsWad { name });
+            wad_indexes.insert(
+                idx,
+                BspNeedsWad {
+                    name,
+                    wads: wads.clone(),
+                },
+            );
         }
     }
 
+    let sequences = LoadedSequences::new(&bsp, &textures, &wads);
+
     // Add debug volumes
     for (idx, model) in bsp.models.iter().enumerate() {
         let mins = model.mins;
@@ -190,137 +396,85 @@ fn load_gold_src_format(
         debug_volumes.push(debug_volume);
     }
 
-    // Add faces
-    {
-        let num_faces = model.num_faces as usize;
-        let first_face = model.idx_first_face as usize;
-
-        for face_idx in first_face..first_face + num_faces {
-            let mut positions = vec![];
-            let mut normals = vec![];
-            let mut colors = vec![];
-            let mut uvs = vec![];
-            let mut idx_miptex = None;
-
-            if let Some(face) = bsp.faces.get(face_idx) {
-                let lighting = bsp.lighting.get(face.lightmap_offset as usize / 3);
-
-                let tex_info = bsp.texture_info.get(face.texture_info as usize);
-                idx_miptex = tex_info.map(|info| info.idx_miptex as usize);
-                let texture = tex_info
-                    .map(|info| bsp.textures.get(info.idx_miptex as usize))
-                    .flatten();
-
-                let tex_name = texture.map(|t| String::from_utf8_lossy(&t.name)).unwrap_or_default();
-                let mut tex_name = tex_name.split('\0');
-                let tex_name = tex_name.next().unwrap_or_default();
-
-                if tex_name == "sky" {
-                    continue;
-                }
+    // Add entities
+    let mut entities = vec![];
 
-                if let Some(plane) = bsp.planes.get(face.plane as usize) {
-                    let mut normal = plane.normal;
+    for entity in bsp.entities.iter() {
+        let bsp_entity = BspEntity::from(entity);
+        let brush_model = entity
+            .brush_model()
+            .map(|idx| bsp.models.get(idx))
+            .flatten();
 
-                    if face.plane_side > 0 {
-                        normal *= -1.0;
-                    }
+        let transform = entity
+            .origin()
+            .map(|origin| Transform::from_translation(vec3tofloat3(origin).into()))
+            .unwrap_or_else(Transform::identity);
 
-                    let num_edges = face.edges as usize;
-                    let first_edge = face.first_edge as usize;
+        let bounds = brush_model.map(|model| BspBrushModel {
+            mins: Vec3::from(vec3tofloat3(model.mins.into())),
+            maxs: Vec3::from(vec3tofloat3(model.maxs.into())),
+        });
 
-                    for surfedge_idx in first_edge..first_edge + num_edges {
-                        if let Some(surf_edge) = bsp.surf_edges.get(surfedge_idx) {
-                            let edge_idx = surf_edge.0;
-                            let edge_idx_abs = edge_idx.abs() as usize;
+        // Triggers are invisible, only their bounds matter
+        if trigger::is_trigger(&bsp_entity.classname) {
+            let trigger = bounds.as_ref().map(|bounds| BspTrigger {
+                mins: bounds.mins,
+                maxs: bounds.maxs,
+            });
 
-                            if let Some(edge) = bsp.edges.get(edge_idx_abs) {
-                                let mut vert0_idx = edge.vertex[0] as usize;
-                                let mut vert1_idx = edge.vertex[1] as usize;
+            entities.push(LoadedEntity {
+                entity: bsp_entity,
+                transform,
+                faces: vec![],
+                bounds,
+                trigger,
+            });
 
-                                if edge_idx < 0 {
-                                    std::mem::swap(&mut vert0_idx, &mut vert1_idx);
-                                }
+            continue;
+        }
 
-                                let vert0 = bsp.vertices.get(vert0_idx);
-
-                                if let Some(vert0) = vert0 {
-                                    let mut color = [0; 3];
-                                    if let Some(colors) = lighting {
-                                        color[0] = colors.r as u32;
-                                        color[0] = colors.g as u32;
-                                        color[0] = colors.b as u32;
-                                    }
-
-                                    let mut u = 0.0;
-                                    let mut v = 0.0;
-                                    if let (Some(tex_info), Some(texture)) = (tex_info, texture) {
-                                        let s_vector = tex_info.s_vector;
-                                        let t_vector = tex_info.t_vector;
-
-                                        u = (vert0.0.dot(s_vector) + tex_info.s_shift)
-                                            / texture.width as f32;
-
-                                        v = (vert0.0.dot(t_vector) + tex_info.t_shift)
-                                            / texture.height as f32;
-                                    }
-
-                                    positions.push(vert0.0);
-                                    //tangents.push(tangent);
-                                    normals.push(normal);
-                                    colors.push(color);
-                                    uvs.push([u, v]);
-                                }
-                            } else {
-                                println!(
-                                    "Can't find edge {} from surfedge {}",
-                                    surf_edge.0.abs(),
-                                    surfedge_idx
-                                );
-                            }
-                        } else {
-                            println!("Can't find surfedge {}", surfedge_idx);
-                        }
-                    }
-                } else {
-                    println!("Can't find plane {}", face.plane);
-                }
-            } else {
-                println!("Can't find face {}", face_idx);
-            }
+        // Conveyors scroll their `scroll` textures, speed defaults like the game's
+        let conveyor_speed = if bsp_entity.classname == "func_conveyor" {
+            Some(
+                bsp_entity
+                    .get("speed")
+                    .and_then(|speed| speed.parse().ok())
+                    .unwrap_or(100.0),
+            )
+        } else {
+            None
+        };
 
-            let indicies = triangulate(&positions);
+        let faces = brush_model
+            .map(|model| load_model_faces(&bsp, model, conveyor_speed, load_context))
+            .unwrap_or_default();
 
-            let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
-            mesh.set_attribute(
-                Mesh::ATTRIBUTE_UV_0,
-                uvs.into_iter().rev().collect::<Vec<_>>(),
-            );
-            mesh.set_attribute(
-                Mesh::ATTRIBUTE_POSITION,
-                positions
-                    .into_iter()
-                    .rev()
-                    .map(vec3tofloat3)
-                    .collect::<Vec<_>>(),
-            );
-            mesh.set_attribute(
-                Mesh::ATTRIBUTE_NORMAL,
-                normals.into_iter().map(vec3tofloat3).collect::<Vec<_>>(),
-            );
-            mesh.set_attribute(
-                Mesh::ATTRIBUTE_COLOR,
-                colors.into_iter().collect::<Vec<_>>(),
-            );
-            mesh.set_indices(Some(Indices::U16(indicies)));
+        entities.push(LoadedEntity {
+            entity: bsp_entity,
+            transform,
+            faces,
+            bounds,
+            trigger: None,
+        });
+    }
 
-            let mesh_label = format!("Mesh{}", face_idx);
-            let mesh = load_context.set_labeled_asset(&mesh_label, LoadedAsset::new(mesh));
+    // Maps without an entity lump still need their world geometry
+    if !bsp.entities.iter().any(|e| e.brush_model() == Some(0)) {
+        let faces = load_model_faces(&bsp, model, None, load_context);
 
-            let face = BspFace { mesh, idx_miptex };
+        let worldspawn = BspEntity {
+            classname: "worldspawn".to_string(),
+            ..Default::default()
+        };
 
-            faces.push(face);
-        }
+        entities.push(LoadedEntity {
+            entity: worldspawn,
+            transform: Transform::identity(),
+            faces,
+            bounds: None,
+            trigger: None,
+        });
     }
 
     let mut world = World::default();
@@ -331,44 +485,50 @@ fn load_gold_src_format(
             let mut map = parent.spawn_bundle((Transform::identity(), GlobalTransform::identity()));
 
             map.with_children(|parent| {
-                // Spawn faces
+                // Spawn entities
                 {
                     let material: StandardMaterial = Color::DARK_GRAY.into();
                     let default_material =
                         load_context.set_labeled_asset("FaceColor", LoadedAsset::new(material));
 
-                    for face in faces.into_iter() {
-                        let texture = face
-                            .idx_miptex
-                            .map(|idx| textures.iter().find(|t| t.idx == idx))
-                            .flatten();
-
-                        let mut entity = parent.spawn_bundle(PbrBundle {
-                            mesh: face.mesh,
-                            material: texture
-                                .map(|t| t.material.clone())
-                                .unwrap_or_else(|| default_material.clone()),
-                            visible: Visible {
-                                is_transparent: texture
-                                    .map(|t| t.is_transparent)
-                                    .unwrap_or_default(),
-                                ..Default::default()
-                            },
-                            ..Default::default()
-                        });
-                        entity.insert(BspMesh);
-
-                        if let Some(needs_wad) = face
-                            .idx_miptex
-                            .map(|idx| wad_indexes.get(&idx))
-                            .flatten()
-                            .cloned()
-                        {
-                            entity.insert(needs_wad);
+                    for loaded in entities.into_iter() {
+                        let mut entity =
+                            parent.spawn_bundle((loaded.transform, GlobalTransform::identity()));
+                        entity.insert(loaded.entity);
+
+                        if let Some(bounds) = loaded.bounds {
+                            entity.insert(bounds);
+                        }
+
+                        if let Some(trigger) = loaded.trigger {
+                            entity.insert(trigger);
+                        }
+
+                        if !loaded.faces.is_empty() {
+                            entity.insert(BspTextureFrame::default());
                         }
+
+                        let faces = loaded.faces;
+                        entity.with_children(|parent| {
+                            for face in faces.into_iter() {
+                                spawn_face(
+                                    parent,
+                                    face,
+                                    &textures,
+                                    &wad_indexes,
+                                    &sequences,
+                                    &default_material,
+                                );
+                            }
+                        });
                     }
                 }
 
+                // Spawn sky, faces using the sky texture aren't drawn so it shows through
+                if let Some(images) = sky {
+                    sky::spawn_skybox(parent, images, load_context);
+                }
+
                 // Spawn debug volumes
                 {
                     let material = StandardMaterial {
@@ -390,13 +550,13 @@ fn load_gold_src_format(
                         let y = (mins[2] + maxs[2]) / 2.0;
                         let z = (mins[0] + maxs[0]) / 2.0;
 
-                        let transform = GlobalTransform::from_xyz(x, y, z);
+                        let transform = Transform::from_xyz(x, y, z);
 
                         parent
                             .spawn_bundle(PbrBundle {
                                 mesh: debug_volumes.mesh,
                                 material: material.clone(),
-                                global_transform: transform,
+                                transform,
                                 visible: Visible {
                                     is_transparent: true,
                                     ..Default::default()
@@ -416,6 +576,210 @@ fn load_gold_src_format(
     Ok(())
 }
 
+fn load_model_faces(
+    bsp: &GoldSrc30Bsp,
+    model: &Model,
+    conveyor_speed: Option<f32>,
+    load_context: &mut LoadContext,
+) -> Vec<BspFace> {
+    let mut faces = vec![];
+
+    let num_faces = model.num_faces as usize;
+    let first_face = model.idx_first_face as usize;
+
+    for face_idx in first_face..first_face + num_faces {
+        let mut positions = vec![];
+        let mut normals = vec![];
+        let mut colors = vec![];
+        let mut uvs = vec![];
+        let mut idx_miptex = None;
+        let mut surface = None;
+
+        if let Some(face) = bsp.faces.get(face_idx) {
+            let lighting = bsp.lighting.get(face.lightmap_offset as usize / 3);
+
+            let tex_info = bsp.texture_info.get(face.texture_info as usize);
+            idx_miptex = tex_info.map(|info| info.idx_miptex as usize);
+            let texture = tex_info
+                .map(|info| bsp.textures.get(info.idx_miptex as usize))
+                .flatten();
+
+            let tex_name = texture
+                .map(|t| String::from_utf8_lossy(&t.name))
+                .unwrap_or_default();
+            let mut tex_name = tex_name.split('\0');
+            let tex_name = tex_name.next().unwrap_or_default();
+
+            if tex_name == "sky" {
+                continue;
+            }
+
+            let texture_size = texture
+                .map(|t| Vec2::new(t.width as f32, t.height as f32))
+                .unwrap_or(Vec2::ONE);
+            surface = Some((tex_name.to_string(), texture_size));
+
+            if let Some(plane) = bsp.planes.get(face.plane as usize) {
+                let mut normal = plane.normal;
+
+                if face.plane_side > 0 {
+                    normal *= -1.0;
+                }
+
+                let num_edges = face.edges as usize;
+                let first_edge = face.first_edge as usize;
+
+                for surfedge_idx in first_edge..first_edge + num_edges {
+                    if let Some(surf_edge) = bsp.surf_edges.get(surfedge_idx) {
+                        let edge_idx = surf_edge.0;
+                        let edge_idx_abs = edge_idx.abs() as usize;
+
+                        if let Some(edge) = bsp.edges.get(edge_idx_abs) {
+                            let mut vert0_idx = edge.vertex[0] as usize;
+                            let mut vert1_idx = edge.vertex[1] as usize;
+
+                            if edge_idx < 0 {
+                                std::mem::swap(&mut vert0_idx, &mut vert1_idx);
+                            }
+
+                            let vert0 = bsp.vertices.get(vert0_idx);
+
+                            if let Some(vert0) = vert0 {
+                                let mut color = [0; 3];
+                                if let Some(colors) = lighting {
+                                    color[0] = colors.r as u32;
+                                    color[0] = colors.g as u32;
+                                    color[0] = colors.b as u32;
+                                }
+
+                                let mut u = 0.0;
+                                let mut v = 0.0;
+                                if let (Some(tex_info), Some(texture)) = (tex_info, texture) {
+                                    let s_vector = tex_info.s_vector;
+                                    let t_vector = tex_info.t_vector;
+
+                                    u = (vert0.0.dot(s_vector) + tex_info.s_shift)
+                                        / texture.width as f32;
+
+                                    v = (vert0.0.dot(t_vector) + tex_info.t_shift)
+                                        / texture.height as f32;
+                                }
+
+                                positions.push(vert0.0);
+                                //tangents.push(tangent);
+                                normals.push(normal);
+                                colors.push(color);
+                                uvs.push([u, v]);
+                            }
+                        } else {
+                            println!(
+                                "Can't find edge {} from surfedge {}",
+                                surf_edge.0.abs(),
+                                surfedge_idx
+                            );
+                        }
+                    } else {
+                        println!("Can't find surfedge {}", surfedge_idx);
+                    }
+                }
+            } else {
+                println!("Can't find plane {}", face.plane);
+            }
+        } else {
+            println!("Can't find face {}", face_idx);
+        }
+
+        let indicies = triangulate(&positions);
+
+        let uvs = uvs.into_iter().rev().collect::<Vec<_>>();
+        let surface = surface
+            .map(|(tex_name, texture_size)| {
+                BspSurfaceAnimation::classify(&tex_name, conveyor_speed, &uvs, texture_size)
+            })
+            .flatten();
+
+        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+        mesh.set_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+        mesh.set_attribute(
+            Mesh::ATTRIBUTE_POSITION,
+            positions
+                .into_iter()
+                .rev()
+                .map(vec3tofloat3)
+                .collect::<Vec<_>>(),
+        );
+        mesh.set_attribute(
+            Mesh::ATTRIBUTE_NORMAL,
+            normals.into_iter().map(vec3tofloat3).collect::<Vec<_>>(),
+        );
+        mesh.set_attribute(
+            Mesh::ATTRIBUTE_COLOR,
+            colors.into_iter().collect::<Vec<_>>(),
+        );
+        mesh.set_indices(Some(Indices::U16(indicies)));
+
+        let mesh_label = format!("Mesh{}", face_idx);
+        let mesh = load_context.set_labeled_asset(&mesh_label, LoadedAsset::new(mesh));
+
+        let face = BspFace {
+            idx: face_idx,
+            mesh,
+            idx_miptex,
+            surface,
+        };
+
+        faces.push(face);
+    }
+
+    faces
+}
+
+fn spawn_face(
+    parent: &mut ChildBuilder,
+    face: BspFace,
+    textures: &[BspTexture],
+    wad_indexes: &HashMap<usize, BspNeedsWad>,
+    sequences: &LoadedSequences,
+    default_material: &Handle<StandardMaterial>,
+) {
+    let idx_miptex = face
+        .idx_miptex
+        .map(|idx| sequences.face_texture(face.idx, idx));
+
+    let texture = idx_miptex
+        .map(|idx| textures.iter().find(|t| t.idx == idx))
+        .flatten();
+
+    let mut entity = parent.spawn_bundle(PbrBundle {
+        mesh: face.mesh,
+        material: texture
+            .map(|t| t.material.clone())
+            .unwrap_or_else(|| default_material.clone()),
+        visible: Visible {
+            is_transparent: texture.map(|t| t.is_transparent).unwrap_or_default(),
+            ..Default::default()
+        },
+        ..Default::default()
+    });
+    entity.insert(BspMesh);
+
+    if let Some(needs_wad) = idx_miptex
+        .map(|idx| wad_indexes.get(&idx))
+        .flatten()
+        .cloned()
+    {
+        entity.insert(needs_wad);
+    }
+
+    if let Some(animation) = idx_miptex.map(|idx| sequences.animation(idx)).flatten() {
+        entity.insert(animation);
+    }
+
+    if let Some(surface) = face.surface {
+        entity.insert(surface);
+    }
+}
+
 fn vec3tofloat3(vec3: glam::Vec3) -> [f32; 3] {
     [vec3.y, vec3.z, vec3.x]
 }
@@ -456,39 +820,15 @@ fn add_wireframes_system(
     }
 }
 
-#[derive(Debug, Default)]
-struct WadManager {
-    textures: HashMap<String, Texture>,
-}
-
-fn load_wads_system(mut manager: ResMut<WadManager>) {
-    let mut wads = vec![];
-
-    if let Ok(dir) = fs::read_dir("assets/wads") {
-        for entry in dir.flatten() {
-            let path = entry.path();
-
-            if let Ok(name) = entry.file_name().into_string() {
-                if name.ends_with(".wad") {
-                    if let Ok(file) = fs::File::open(path) {
-                        let reader = BufReader::new(file);
-
-                        match WadDecoder::from_reader(reader).decode() {
-                            Ok(wad) => wads.push(wad),
-                            Err(e) => {
-                                dbg!(&e);
-                            }
-                        }
-                    }
-                }
-            }
-        }
-    }
-
-    for wad in wads.into_iter() {
-        for (name, texture) in wad.textures.into_iter() {
-            if texture.width > 0 && texture.height > 0 {
-                manager.textures.insert(name, texture);
+fn run_spawn_hooks_system(
+    mut commands: Commands,
+    hooks: Res<BspSpawnHooks>,
+    query: Query<(Entity, &BspEntity), Added<BspEntity>>,
+) {
+    for (entity, bsp_entity) in query.iter() {
+        if let Some(hooks) = hooks.hooks.get(&bsp_entity.classname) {
+            for hook in hooks.iter() {
+                hook(&mut commands, entity, bsp_entity);
             }
         }
     }
@@ -535,59 +875,3 @@ fn parse_texture(texture: &Texture) -> (bool, BevyTexture) {
         },
     )
 }
-
-fn apply_wad_textures_system(
-    mut commands: Commands,
-    mut query: Query<(Entity, &BspNeedsWad, &mut Visible)>,
-    manager: Res<WadManager>,
-    mut textures: ResMut<Assets<BevyTexture>>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
-) {
-    let mut missing_textures = HashSet::new();
-    let mut mat_handles = HashMap::new();
-
-    for (_, wad, _) in query.iter_mut() {
-        missing_textures.insert(wad.name.clone());
-    }
-
-    for name in missing_textures.iter() {
-        if let Some(texture) = manager.textures.get(name) {
-            let (transparent, texture) = parse_texture(&texture);
-
-            let tex_handle = textures.add(texture);
-
-            let material = StandardMaterial {
-                base_color_texture: Some(tex_handle),
-                unlit: true,
-                ..Default::default()
-            };
-            let handle = materials.add(material);
-
-            mat_handles.insert(name.clone(), (transparent, handle));
-        }
-    }
-
-    missing_textures.drain();
-
-    if !mat_handles.is_empty() {
-        for (entity, wad, mut visible) in query.iter_mut() {
-            let mut entity_commands = commands.entity(entity);
-
-            if let Some((is_transparent, material)) = mat_handles.get(&wad.name).cloned() {
-                visible.is_transparent = is_transparent;
-
-                entity_commands.remove::<Handle<StandardMaterial>>();
-                entity_commands.insert(material);
-            } else {
-                missing_textures.insert(wad.name.clone());
-            }
-
-            // No point in trying after first time
-            entity_commands.remove::<BspNeedsWad>();
-        }
-    }
-
-    for name in missing_textures {
-        warn!("WAD texture missing: {}", name);
-    }
-}
@@ -0,0 +1,273 @@
+use bevy::prelude::*;
+
+use crate::{BspBrushModel, BspEntity};
+
+/// Fired to activate a mover, like a player pressing `use` on it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BspUseEvent(pub Entity);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BspMoverKind {
+    Door,
+    Plat,
+    Train,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum MoverState {
+    AtStart,
+    AtEnd { wait: f32 },
+    ToStart,
+    ToEnd,
+    Stopped,
+    ToCorner { name: String },
+    AtCorner { name: String, wait: f32 },
+}
+
+/// Brush model that moves between two positions, or along a `path_corner` chain
+#[derive(Debug, Clone)]
+pub struct BspMover {
+    pub kind: BspMoverKind,
+    pub speed: f32,
+    pub wait: f32,
+    start: Vec3,
+    end: Vec3,
+    center: Vec3,
+    state: MoverState,
+}
+
+impl BspMover {
+    fn new(entity: &BspEntity, model: &BspBrushModel, origin: Vec3) -> Option<BspMover> {
+        let float = |key: &str| entity.get(key).and_then(|v| v.trim().parse::<f32>().ok());
+
+        let size = model.maxs - model.mins;
+        let center = (model.mins + model.maxs) / 2.0;
+
+        let (kind, speed, start, end) = match entity.classname.as_str() {
+            "func_door" => {
+                // Hammer writes `angles` instead, with the yaw second
+                let angle = float("angle").or_else(|| {
+                    entity
+                        .get("angles")
+                        .and_then(|v| v.split_whitespace().nth(1))
+                        .and_then(|v| v.parse().ok())
+                });
+                let direction = move_direction(angle.unwrap_or_default());
+                let lip = float("lip").unwrap_or_default();
+                let distance = direction.abs().dot(size) - lip;
+
+                let speed = float("speed").unwrap_or(100.0);
+
+                (
+                    BspMoverKind::Door,
+                    speed,
+                    origin,
+                    origin + direction * distance,
+                )
+            }
+            "func_plat" => {
+                let height = float("height").unwrap_or(size.y - 8.0);
+                let bottom = origin - Vec3::Y * height;
+
+                let speed = float("speed").unwrap_or(150.0);
+
+                (BspMoverKind::Plat, speed, bottom, origin)
+            }
+            "func_train" => {
+                let speed = float("speed").unwrap_or(100.0);
+
+                (BspMoverKind::Train, speed, origin, origin)
+            }
+            _ => return None,
+        };
+
+        let state = if kind == BspMoverKind::Train {
+            MoverState::Stopped
+        } else {
+            MoverState::AtStart
+        };
+
+        Some(BspMover {
+            kind,
+            speed,
+            wait: float("wait").unwrap_or(4.0),
+            start,
+            end,
+            center,
+            state,
+        })
+    }
+
+    fn toggle(&mut self, first_corner: Option<&str>) {
+        self.state = match std::mem::replace(&mut self.state, MoverState::Stopped) {
+            MoverState::AtStart | MoverState::ToStart => MoverState::ToEnd,
+            MoverState::AtEnd { .. } | MoverState::ToEnd => MoverState::ToStart,
+            MoverState::Stopped => match first_corner {
+                Some(name) => MoverState::ToCorner {
+                    name: name.to_string(),
+                },
+                None => MoverState::Stopped,
+            },
+            MoverState::ToCorner { .. } | MoverState::AtCorner { .. } => MoverState::Stopped,
+        };
+    }
+}
+
+/// Direction from the `angle` yaw, `-1` is up and `-2` is down
+fn move_direction(angle: f32) -> Vec3 {
+    if (angle + 1.0).abs() < f32::EPSILON {
+        Vec3::Y
+    } else if (angle + 2.0).abs() < f32::EPSILON {
+        -Vec3::Y
+    } else {
+        let radians = angle.to_radians();
+
+        // Same swizzle as the map geometry
+        Vec3::new(radians.sin(), 0.0, radians.cos())
+    }
+}
+
+fn move_towards(current: Vec3, destination: Vec3, distance: f32) -> (Vec3, bool) {
+    let delta = destination - current;
+    let length = delta.length();
+
+    if length <= distance {
+        (destination, true)
+    } else {
+        (current + delta / length * distance, false)
+    }
+}
+
+fn find_corner<'a>(
+    mut corners: impl Iterator<Item = (&'a BspEntity, &'a Transform)>,
+    name: &str,
+) -> Option<(&'a BspEntity, &'a Transform)> {
+    corners.find(|(entity, _)| {
+        entity.classname == "path_corner" && entity.get("targetname") == Some(name)
+    })
+}
+
+pub(crate) fn setup_movers_system(
+    mut commands: Commands,
+    query: Query<(Entity, &BspEntity, &BspBrushModel, &Transform), Added<BspEntity>>,
+    corners: Query<(&BspEntity, &Transform)>,
+) {
+    for (entity, bsp_entity, model, transform) in query.iter() {
+        if let Some(mover) = BspMover::new(bsp_entity, model, transform.translation) {
+            // Plats start lowered and trains at their first corner
+            let translation = match mover.kind {
+                BspMoverKind::Door => None,
+                BspMoverKind::Plat => Some(mover.start),
+                BspMoverKind::Train => bsp_entity
+                    .get("target")
+                    .and_then(|target| find_corner(corners.iter(), target))
+                    .map(|(_, corner)| corner.translation - mover.center),
+            };
+
+            if let Some(translation) = translation {
+                commands.entity(entity).insert(Transform {
+                    translation,
+                    ..*transform
+                });
+            }
+
+            commands.entity(entity).insert(mover);
+        }
+    }
+}
+
+pub(crate) fn use_movers_system(
+    mut events: EventReader<BspUseEvent>,
+    mut query: Query<(&mut BspMover, &BspEntity)>,
+) {
+    for BspUseEvent(entity) in events.iter() {
+        if let Ok((mut mover, bsp_entity)) = query.get_mut(*entity) {
+            let first_corner = bsp_entity.get("target");
+
+            mover.toggle(first_corner);
+        }
+    }
+}
+
+pub(crate) fn move_movers_system(
+    time: Res<Time>,
+    mut movers: Query<(&mut BspMover, &mut Transform)>,
+    corners: Query<(&BspEntity, &Transform), Without<BspMover>>,
+) {
+    let delta = time.delta_seconds();
+
+    for (mut mover, mut transform) in movers.iter_mut() {
+        let distance = mover.speed * delta;
+
+        mover.state = match std::mem::replace(&mut mover.state, MoverState::Stopped) {
+            MoverState::ToEnd => {
+                let (translation, arrived) =
+                    move_towards(transform.translation, mover.end, distance);
+                transform.translation = translation;
+
+                if arrived {
+                    MoverState::AtEnd { wait: mover.wait }
+                } else {
+                    MoverState::ToEnd
+                }
+            }
+            MoverState::ToStart => {
+                let (translation, arrived) =
+                    move_towards(transform.translation, mover.start, distance);
+                transform.translation = translation;
+
+                if arrived {
+                    MoverState::AtStart
+                } else {
+                    MoverState::ToStart
+                }
+            }
+            // Negative wait stays open until used again
+            MoverState::AtEnd { wait } if wait >= 0.0 => {
+                if wait - delta <= 0.0 {
+                    MoverState::ToStart
+                } else {
+                    MoverState::AtEnd { wait: wait - delta }
+                }
+            }
+            MoverState::ToCorner { name } => match find_corner(corners.iter(), &name) {
+                Some((corner, corner_transform)) => {
+                    let destination = corner_transform.translation - mover.center;
+                    let (translation, arrived) =
+                        move_towards(transform.translation, destination, distance);
+                    transform.translation = translation;
+
+                    if arrived {
+                        let wait = corner
+                            .get("wait")
+                            .and_then(|v| v.trim().parse().ok())
+                            .unwrap_or_default();
+
+                        MoverState::AtCorner { name, wait }
+                    } else {
+                        MoverState::ToCorner { name }
+                    }
+                }
+                None => MoverState::Stopped,
+            },
+            MoverState::AtCorner { name, wait } => {
+                if wait - delta > 0.0 {
+                    MoverState::AtCorner {
+                        name,
+                        wait: wait - delta,
+                    }
+                } else {
+                    match find_corner(corners.iter(), &name)
+                        .and_then(|(corner, _)| corner.get("target"))
+                    {
+                        Some(next) => MoverState::ToCorner {
+                            name: next.to_string(),
+                        },
+                        None => MoverState::Stopped,
+                    }
+                }
+            }
+            state => state,
+        };
+    }
+}
@@ -0,0 +1,192 @@
+use std::collections::{HashMap, HashSet};
+
+use bevy::prelude::*;
+
+use crate::{BspEntity, BspMesh};
+
+/// The `rendermode` key of a brush entity
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BspRenderModeKind {
+    Normal,
+    Color,
+    Texture,
+    /// Alpha blended like `Texture`, the pbr pipeline can't blend additively
+    Glow,
+    Solid,
+    /// Alpha blended like `Texture`, the pbr pipeline can't blend additively
+    Additive,
+}
+
+/// How the faces of a brush entity are drawn, from its `rendermode`, `renderamt`
+/// and `rendercolor` keys. Not added for entities drawn normally
+#[derive(Debug, Clone, PartialEq)]
+pub struct BspRenderMode {
+    pub kind: BspRenderModeKind,
+    pub amount: u8,
+    pub color: [u8; 3],
+}
+
+impl BspRenderMode {
+    fn new(entity: &BspEntity) -> Option<BspRenderMode> {
+        let kind = match entity.get("rendermode").map(str::trim) {
+            Some("1") => BspRenderModeKind::Color,
+            Some("2") => BspRenderModeKind::Texture,
+            Some("3") => BspRenderModeKind::Glow,
+            Some("4") => BspRenderModeKind::Solid,
+            Some("5") => BspRenderModeKind::Additive,
+            _ => return None,
+        };
+
+        // Like the game, a missing renderamt makes the entity invisible
+        let amount = entity
+            .get("renderamt")
+            .and_then(|v| v.trim().parse::<f32>().ok())
+            .map(|v| v.max(0.0).min(255.0) as u8)
+            .unwrap_or_default();
+
+        let mut color = [0; 3];
+        for (channel, value) in color.iter_mut().zip(
+            entity
+                .get("rendercolor")
+                .unwrap_or_default()
+                .split_whitespace(),
+        ) {
+            *channel = value.parse::<f32>().unwrap_or_default().max(0.0).min(255.0) as u8;
+        }
+
+        Some(BspRenderMode {
+            kind,
+            amount,
+            color,
+        })
+    }
+
+    /// Variant of a face material for this mode. The pbr pipeline only alpha
+    /// blends, so glow and additive are drawn translucent like texture
+    fn material(&self, material: &StandardMaterial) -> StandardMaterial {
+        let alpha = self.amount as f32 / 255.0;
+        let mut material = material.clone();
+
+        match self.kind {
+            BspRenderModeKind::Color => {
+                let [r, g, b] = self.color;
+
+                material.base_color = Color::rgba_u8(r, g, b, self.amount);
+                material.base_color_texture = None;
+            }
+            BspRenderModeKind::Texture | BspRenderModeKind::Glow | BspRenderModeKind::Additive => {
+                material.base_color = Color::rgba(1.0, 1.0, 1.0, alpha);
+            }
+            BspRenderModeKind::Normal | BspRenderModeKind::Solid => {}
+        }
+
+        material
+    }
+
+    /// Solid cuts out the blue pixels of `{` textures, the others blend
+    fn is_transparent(&self) -> bool {
+        match self.kind {
+            BspRenderModeKind::Normal => false,
+            BspRenderModeKind::Solid => true,
+            _ => self.amount < 255,
+        }
+    }
+}
+
+type RenderModeKey = (u8, u8, [u8; 3]);
+
+/// Materials created for render modes, shared between faces using the same
+/// texture and mode, and with texture animations picking their next frame
+#[derive(Default)]
+pub(crate) struct RenderModeMaterials {
+    variants: HashMap<(Handle<StandardMaterial>, RenderModeKey), Handle<StandardMaterial>>,
+    created: HashSet<Handle<StandardMaterial>>,
+}
+
+impl RenderModeMaterials {
+    /// The render mode variant of a material, created the first time it's
+    /// asked for. Variants are returned as they are
+    pub(crate) fn variant(
+        &mut self,
+        materials: &mut Assets<StandardMaterial>,
+        material: &Handle<StandardMaterial>,
+        render_mode: &BspRenderMode,
+    ) -> Option<Handle<StandardMaterial>> {
+        if self.created.contains(material) {
+            return Some(material.clone());
+        }
+
+        let key = (
+            render_mode.kind as u8,
+            render_mode.amount,
+            render_mode.color,
+        );
+
+        if let Some(variant) = self.variants.get(&(material.clone(), key)) {
+            return Some(variant.clone());
+        }
+
+        let variant = render_mode.material(materials.get(material)?);
+        let variant = materials.add(variant);
+
+        self.variants
+            .insert((material.clone(), key), variant.clone());
+        self.created.insert(variant.clone());
+
+        Some(variant)
+    }
+}
+
+pub(crate) fn setup_render_modes_system(
+    mut commands: Commands,
+    query: Query<(Entity, &BspEntity), Added<BspEntity>>,
+) {
+    for (entity, bsp_entity) in query.iter() {
+        if let Some(render_mode) = BspRenderMode::new(bsp_entity) {
+            commands.entity(entity).insert(render_mode);
+        }
+    }
+}
+
+/// Swaps face materials for their render mode variant whenever the entity gets
+/// its mode or a face gets a new material, from a WAD or a texture animation
+pub(crate) fn apply_render_modes_system(
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut cache: ResMut<RenderModeMaterials>,
+    added: Query<&Children, Added<BspRenderMode>>,
+    render_modes: Query<&BspRenderMode>,
+    mut faces: QuerySet<(
+        Query<Entity, (With<BspMesh>, Changed<Handle<StandardMaterial>>)>,
+        Query<(&Parent, &mut Handle<StandardMaterial>, &mut Visible), With<BspMesh>>,
+    )>,
+) {
+    let mut changed = faces.q0().iter().collect::<Vec<_>>();
+
+    for children in added.iter() {
+        changed.extend(children.iter().copied());
+    }
+
+    for entity in changed {
+        let (parent, mut material, mut visible) = match faces.q1_mut().get_mut(entity) {
+            Ok(face) => face,
+            Err(_) => continue,
+        };
+
+        let render_mode = match render_modes.get(parent.0) {
+            Ok(render_mode) => render_mode,
+            Err(_) => continue,
+        };
+
+        let variant = match cache.variant(&mut materials, &material, render_mode) {
+            Some(variant) => variant,
+            None => continue,
+        };
+
+        visible.is_transparent |= render_mode.is_transparent();
+
+        // Animated faces already switch to variants, so they aren't changed again
+        if *material != variant {
+            *material = variant;
+        }
+    }
+}
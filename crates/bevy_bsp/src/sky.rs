@@ -0,0 +1,153 @@
+use std::io::Cursor;
+
+use bevy::asset::{LoadContext, LoadedAsset};
+use bevy::prelude::{Texture as BevyTexture, *};
+use bevy::render::camera::PerspectiveProjection;
+use bevy::render::mesh::Indices;
+use bevy::render::pipeline::PrimitiveTopology;
+use bevy::render::texture::Extent3d;
+use decoder::tga::Image;
+use decoder::TgaDecoder;
+
+use crate::vec3tofloat3;
+
+/// Half the size of the skybox, past the 8192 units across any map fits in,
+/// so world geometry seen from inside the map is always in front of it
+const SKY_SIZE: f32 = 16384.0;
+
+/// Far plane a camera needs to reach the corners of the skybox
+pub const SKY_FAR_PLANE: f32 = SKY_SIZE * 2.0;
+
+/// Suffix of each sky image, with its direction and image axes in map space
+const SKY_SIDES: [(&str, [f32; 3], [f32; 3], [f32; 3]); 6] = [
+    ("rt", [1.0, 0.0, 0.0], [0.0, -1.0, 0.0], [0.0, 0.0, -1.0]),
+    ("lf", [-1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, -1.0]),
+    ("bk", [0.0, 1.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, -1.0]),
+    ("ft", [0.0, -1.0, 0.0], [-1.0, 0.0, 0.0], [0.0, 0.0, -1.0]),
+    ("up", [0.0, 0.0, 1.0], [0.0, -1.0, 0.0], [1.0, 0.0, 0.0]),
+    ("dn", [0.0, 0.0, -1.0], [0.0, -1.0, 0.0], [-1.0, 0.0, 0.0]),
+];
+
+#[derive(Debug, Clone, Reflect, Default)]
+#[reflect(Component)]
+pub struct BspSky;
+
+/// Reads the six `gfx/env/<skyname><side>.tga` images, all of them must exist
+pub(crate) async fn load_sky_images(
+    skyname: &str,
+    load_context: &LoadContext<'_>,
+) -> Option<Vec<Image>> {
+    let mut images = Vec::with_capacity(SKY_SIDES.len());
+
+    for (suffix, ..) in SKY_SIDES.iter() {
+        let path = format!("gfx/env/{}{}.tga", skyname, suffix);
+
+        let image = match load_context.read_asset_bytes(&path).await {
+            Ok(bytes) => TgaDecoder::from_reader(Cursor::new(bytes)).decode(),
+            Err(e) => {
+                warn!("Sky image missing: {}: {}", path, e);
+                return None;
+            }
+        };
+
+        match image {
+            Ok(image) => images.push(image),
+            Err(e) => {
+                warn!("Sky image invalid: {}: {}", path, e);
+                return None;
+            }
+        }
+    }
+
+    Some(images)
+}
+
+pub(crate) fn spawn_skybox(
+    parent: &mut ChildBuilder,
+    images: Vec<Image>,
+    load_context: &mut LoadContext,
+) {
+    let mut sides = vec![];
+
+    for (idx, (image, (_, normal, right, down))) in
+        images.into_iter().zip(SKY_SIDES.iter()).enumerate()
+    {
+        let texture = BevyTexture {
+            data: image.data,
+            size: Extent3d {
+                width: image.width,
+                height: image.height,
+                depth_or_array_layers: 1,
+            },
+            ..Default::default()
+        };
+        let texture = load_context
+            .set_labeled_asset(&format!("SkyTexture{}", idx), LoadedAsset::new(texture));
+
+        let material = StandardMaterial {
+            base_color_texture: Some(texture),
+            unlit: true,
+            ..Default::default()
+        };
+        let material = load_context
+            .set_labeled_asset(&format!("SkyMaterial{}", idx), LoadedAsset::new(material));
+
+        let mesh = sky_side_mesh((*normal).into(), (*right).into(), (*down).into());
+        let mesh =
+            load_context.set_labeled_asset(&format!("SkyMesh{}", idx), LoadedAsset::new(mesh));
+
+        sides.push((mesh, material));
+    }
+
+    parent
+        .spawn_bundle((Transform::identity(), GlobalTransform::identity()))
+        .insert(BspSky)
+        .with_children(|parent| {
+            for (mesh, material) in sides.into_iter() {
+                parent.spawn_bundle(PbrBundle {
+                    mesh,
+                    material,
+                    ..Default::default()
+                });
+            }
+        });
+}
+
+/// Quad facing the inside of the skybox, the image's top left is at uv 0, 0
+fn sky_side_mesh(normal: glam::Vec3, right: glam::Vec3, down: glam::Vec3) -> Mesh {
+    let center = normal * SKY_SIZE;
+    let right = right * SKY_SIZE;
+    let down = down * SKY_SIZE;
+
+    let positions = vec![
+        center - right - down,
+        center + right - down,
+        center + right + down,
+        center - right + down,
+    ];
+
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    mesh.set_attribute(
+        Mesh::ATTRIBUTE_POSITION,
+        positions.into_iter().map(vec3tofloat3).collect::<Vec<_>>(),
+    );
+    mesh.set_attribute(Mesh::ATTRIBUTE_NORMAL, vec![vec3tofloat3(-normal); 4]);
+    mesh.set_attribute(
+        Mesh::ATTRIBUTE_UV_0,
+        vec![[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]],
+    );
+    mesh.set_indices(Some(Indices::U16(vec![0, 2, 1, 0, 3, 2])));
+
+    mesh
+}
+
+pub(crate) fn sky_follow_camera_system(
+    cameras: Query<&GlobalTransform, With<PerspectiveProjection>>,
+    mut skies: Query<&mut Transform, With<BspSky>>,
+) {
+    if let Some(camera) = cameras.iter().next() {
+        for mut transform in skies.iter_mut() {
+            transform.translation = camera.translation;
+        }
+    }
+}
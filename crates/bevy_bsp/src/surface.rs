@@ -0,0 +1,80 @@
+use bevy::prelude::*;
+
+/// Amplitude of the water warp, in texels
+const WARP_AMPLITUDE: f32 = 8.0;
+
+/// Face whose texture coordinates move every frame. `!` textures warp like
+/// water, `scroll` textures on a func_conveyor scroll by the entity's speed
+#[derive(Debug, Clone, Reflect, Default)]
+#[reflect(Component)]
+pub struct BspSurfaceAnimation {
+    pub warp: bool,
+    pub scroll_speed: f32,
+    uvs: Vec<Vec2>,
+    texture_size: Vec2,
+}
+
+impl BspSurfaceAnimation {
+    /// Classifies a face by its texture name, `None` for static surfaces
+    pub(crate) fn classify(
+        tex_name: &str,
+        conveyor_speed: Option<f32>,
+        uvs: &[[f32; 2]],
+        texture_size: Vec2,
+    ) -> Option<BspSurfaceAnimation> {
+        let tex_name = tex_name.to_lowercase();
+
+        let warp = tex_name.starts_with('!');
+        let scroll_speed = conveyor_speed
+            .filter(|_| tex_name.starts_with("scroll"))
+            .unwrap_or_default();
+
+        if !warp && scroll_speed == 0.0 {
+            return None;
+        }
+
+        Some(BspSurfaceAnimation {
+            warp,
+            scroll_speed,
+            uvs: uvs.iter().map(|uv| Vec2::from(*uv)).collect(),
+            texture_size,
+        })
+    }
+
+    fn uv(&self, uv: Vec2, time: f32) -> [f32; 2] {
+        let texels = uv * self.texture_size;
+        let mut offset = Vec2::ZERO;
+
+        if self.warp {
+            offset += Vec2::new(
+                (texels.y * 0.125 + time).sin(),
+                (texels.x * 0.125 + time).sin(),
+            ) * WARP_AMPLITUDE;
+        }
+
+        // Wrapped so the offset keeps its precision on long sessions
+        offset.x += (time * self.scroll_speed) % self.texture_size.x;
+
+        (uv + offset / self.texture_size).into()
+    }
+}
+
+pub(crate) fn animate_surfaces_system(
+    time: Res<Time>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    query: Query<(&BspSurfaceAnimation, &Handle<Mesh>)>,
+) {
+    let time = time.seconds_since_startup() as f32;
+
+    for (animation, mesh) in query.iter() {
+        if let Some(mesh) = meshes.get_mut(mesh) {
+            let uvs = animation
+                .uvs
+                .iter()
+                .map(|uv| animation.uv(*uv, time))
+                .collect::<Vec<_>>();
+
+            mesh.set_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+        }
+    }
+}
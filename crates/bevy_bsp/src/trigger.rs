@@ -0,0 +1,66 @@
+use std::collections::HashSet;
+
+use bevy::prelude::*;
+
+/// Invisible volume spawned for `trigger_*` brush entities
+#[derive(Debug, Clone, Reflect, Default)]
+#[reflect(Component)]
+pub struct BspTrigger {
+    pub mins: Vec3,
+    pub maxs: Vec3,
+}
+
+impl BspTrigger {
+    pub fn contains(&self, point: Vec3) -> bool {
+        point.x >= self.mins.x
+            && point.y >= self.mins.y
+            && point.z >= self.mins.z
+            && point.x <= self.maxs.x
+            && point.y <= self.maxs.y
+            && point.z <= self.maxs.z
+    }
+}
+
+/// Marks an entity, such as the camera, that fires trigger events
+#[derive(Debug, Clone, Reflect, Default)]
+#[reflect(Component)]
+pub struct BspTriggerActivator;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BspTriggerEvent {
+    Enter { trigger: Entity, activator: Entity },
+    Exit { trigger: Entity, activator: Entity },
+}
+
+pub(crate) fn is_trigger(classname: &str) -> bool {
+    classname.starts_with("trigger_")
+}
+
+pub(crate) fn trigger_system(
+    mut touching: Local<HashSet<(Entity, Entity)>>,
+    mut events: EventWriter<BspTriggerEvent>,
+    triggers: Query<(Entity, &BspTrigger, &GlobalTransform)>,
+    activators: Query<(Entity, &GlobalTransform), With<BspTriggerActivator>>,
+) {
+    let mut current = HashSet::new();
+
+    for (trigger_entity, trigger, trigger_transform) in triggers.iter() {
+        for (activator, transform) in activators.iter() {
+            let local = transform.translation - trigger_transform.translation;
+
+            if trigger.contains(local) {
+                current.insert((trigger_entity, activator));
+            }
+        }
+    }
+
+    for &(trigger, activator) in current.difference(&touching) {
+        events.send(BspTriggerEvent::Enter { trigger, activator });
+    }
+
+    for &(trigger, activator) in touching.difference(&current) {
+        events.send(BspTriggerEvent::Exit { trigger, activator });
+    }
+
+    *touching = current;
+}
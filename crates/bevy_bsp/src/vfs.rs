@@ -0,0 +1,87 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use bevy::asset::{create_platform_default_asset_io, AssetIo, AssetIoError};
+use bevy::prelude::*;
+use bevy::tasks::IoTaskPool;
+use bevy::utils::BoxedFuture;
+use decoder::vfs::Vfs;
+
+/// The mounted game directories and paks, to list maps and other files
+#[derive(Debug, Clone)]
+pub struct BspVfs(pub Arc<Vfs>);
+
+/// Reads assets from the VFS first, then from the regular asset folder. Only
+/// files in the asset folder are watched for changes
+pub struct VfsAssetIo {
+    vfs: Arc<Vfs>,
+    fallback: Box<dyn AssetIo>,
+}
+
+impl AssetIo for VfsAssetIo {
+    fn load_path<'a>(&'a self, path: &'a Path) -> BoxedFuture<'a, Result<Vec<u8>, AssetIoError>> {
+        Box::pin(async move {
+            match self.vfs.read(&path.to_string_lossy()) {
+                Ok(bytes) => Ok(bytes),
+                Err(_) => self.fallback.load_path(path).await,
+            }
+        })
+    }
+
+    fn read_directory(
+        &self,
+        path: &Path,
+    ) -> Result<Box<dyn Iterator<Item = PathBuf>>, AssetIoError> {
+        let mut paths = self
+            .vfs
+            .read_dir(&path.to_string_lossy())
+            .into_iter()
+            .map(PathBuf::from)
+            .collect::<Vec<_>>();
+
+        if let Ok(fallback) = self.fallback.read_directory(path) {
+            paths.extend(fallback);
+        } else if paths.is_empty() {
+            return Err(AssetIoError::NotFound(path.to_path_buf()));
+        }
+
+        Ok(Box::new(paths.into_iter()))
+    }
+
+    fn is_directory(&self, path: &Path) -> bool {
+        self.vfs.is_dir(&path.to_string_lossy()) || self.fallback.is_directory(path)
+    }
+
+    fn watch_path_for_changes(&self, path: &Path) -> Result<(), AssetIoError> {
+        self.fallback.watch_path_for_changes(path)
+    }
+
+    fn watch_for_changes(&self) -> Result<(), AssetIoError> {
+        self.fallback.watch_for_changes()
+    }
+}
+
+/// Makes the `AssetServer` resolve maps, WADs, skies and sprites through a
+/// [`Vfs`]. Add it before `AssetPlugin`
+pub struct BspVfsPlugin {
+    pub vfs: Arc<Vfs>,
+}
+
+impl Plugin for BspVfsPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        let task_pool = app
+            .world()
+            .get_resource::<IoTaskPool>()
+            .expect("`IoTaskPool` resource not found.")
+            .0
+            .clone();
+
+        let asset_io = VfsAssetIo {
+            vfs: self.vfs.clone(),
+            fallback: create_platform_default_asset_io(app),
+        };
+
+        app.insert_resource(AssetServer::new(asset_io, task_pool))
+            .insert_resource(BspVfs(self.vfs.clone()));
+    }
+}
@@ -0,0 +1,250 @@
+use std::collections::{HashMap, HashSet};
+use std::io::Cursor;
+use std::sync::Mutex;
+
+use bevy::asset::{AssetLoader, LoadContext, LoadState, LoadedAsset};
+use bevy::prelude::{Texture as BevyTexture, *};
+use bevy::reflect::TypeUuid;
+use bevy::utils::BoxedFuture;
+use decoder::format::gold_src_30::Texture;
+use decoder::wad::WadIndex;
+use decoder::WadDecoder;
+
+use crate::{parse_texture, BspConfig, BspNeedsWad};
+
+/// A `.wad` file, its textures are decoded when a map asks for them
+#[derive(Debug, TypeUuid)]
+#[uuid = "8e3b1f4a-52c6-4d0e-9a57-3f0c6d2b7e91"]
+pub struct BspWad {
+    index: Mutex<WadIndex<Cursor<Vec<u8>>>>,
+}
+
+impl BspWad {
+    pub fn contains(&self, name: &str) -> bool {
+        self.index.lock().unwrap().find(name).is_some()
+    }
+
+    /// Decodes a texture by name, in any case
+    pub fn texture(&self, name: &str) -> Option<Texture> {
+        self.index
+            .lock()
+            .unwrap()
+            .texture(name)
+            .filter(|texture| texture.width > 0 && texture.height > 0)
+    }
+}
+
+#[derive(Default)]
+pub struct WadFileLoader;
+
+impl AssetLoader for WadFileLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let index = WadDecoder::from_reader(Cursor::new(bytes.to_vec())).index()?;
+
+            load_context.set_default_asset(LoadedAsset::new(BspWad {
+                index: Mutex::new(index),
+            }));
+
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["wad"]
+    }
+}
+
+/// WADs requested by maps, with the materials made from their textures
+#[derive(Debug, Default)]
+pub(crate) struct WadManager {
+    /// Candidates for each WAD file name, one per search directory in order
+    wads: HashMap<String, Vec<Handle<BspWad>>>,
+    materials: HashMap<(Handle<BspWad>, String), (bool, Handle<StandardMaterial>)>,
+    reported: HashSet<String>,
+}
+
+impl WadManager {
+    /// Starts loading the WADs a map declares from every search directory
+    fn request(&mut self, names: &[String], dirs: &[String], asset_server: &AssetServer) {
+        for name in names {
+            self.wads.entry(name.clone()).or_insert_with(|| {
+                dirs.iter()
+                    .map(|dir| match dir.trim_end_matches('/') {
+                        "" => asset_server.load(name.as_str()),
+                        dir => asset_server.load(format!("{}/{}", dir, name).as_str()),
+                    })
+                    .collect()
+            });
+        }
+    }
+
+    /// Whether every candidate of these WADs finished loading, or failed to
+    fn is_loaded(&self, names: &[String], asset_server: &AssetServer) -> bool {
+        names
+            .iter()
+            .filter_map(|name| self.wads.get(name))
+            .flatten()
+            .all(|handle| {
+                matches!(
+                    asset_server.get_load_state(handle),
+                    LoadState::Loaded | LoadState::Failed
+                )
+            })
+    }
+
+    /// The first search directory holding the WAD wins
+    fn resolve<'a>(
+        &self,
+        name: &str,
+        wads: &'a Assets<BspWad>,
+    ) -> Option<(Handle<BspWad>, &'a BspWad)> {
+        self.wads
+            .get(name)?
+            .iter()
+            .find_map(|handle| wads.get(handle).map(|wad| (handle.clone(), wad)))
+    }
+
+    /// Material for a texture from the first WAD in `names` that has it,
+    /// created on first use and shared between maps
+    pub(crate) fn material(
+        &mut self,
+        name: &str,
+        names: &[String],
+        wads: &Assets<BspWad>,
+        textures: &mut Assets<BevyTexture>,
+        materials: &mut Assets<StandardMaterial>,
+    ) -> Option<(bool, Handle<StandardMaterial>)> {
+        let (handle, wad) = names
+            .iter()
+            .filter_map(|wad| self.resolve(wad, wads))
+            .find(|(_, wad)| wad.contains(name))?;
+
+        let key = (handle, name.to_string());
+
+        if let Some(material) = self.materials.get(&key) {
+            return Some(material.clone());
+        }
+
+        let (transparent, texture) = parse_texture(&wad.texture(name)?);
+
+        let material = StandardMaterial {
+            base_color_texture: Some(textures.add(texture)),
+            unlit: true,
+            ..Default::default()
+        };
+        let material = (transparent, materials.add(material));
+
+        self.materials.insert(key, material.clone());
+
+        Some(material)
+    }
+
+    /// Warns once about each declared WAD no search directory has
+    fn report_missing(&mut self, names: &[String], dirs: &[String], wads: &Assets<BspWad>) {
+        for name in names {
+            if self.resolve(name, wads).is_none() && self.reported.insert(name.clone()) {
+                warn!("WAD missing: {}, searched {:?}", name, dirs);
+            }
+        }
+    }
+}
+
+/// Swaps the texture of materials already made from a WAD that changed on disk
+pub(crate) fn reload_wads_system(
+    mut events: EventReader<AssetEvent<BspWad>>,
+    manager: Res<WadManager>,
+    wads: Res<Assets<BspWad>>,
+    mut textures: ResMut<Assets<BevyTexture>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    for event in events.iter() {
+        let handle = match event {
+            AssetEvent::Modified { handle } => handle,
+            _ => continue,
+        };
+
+        let wad = match wads.get(handle) {
+            Some(wad) => wad,
+            None => continue,
+        };
+
+        for ((wad_handle, name), (_, material)) in manager.materials.iter() {
+            if wad_handle != handle {
+                continue;
+            }
+
+            if let (Some(texture), Some(material)) =
+                (wad.texture(name), materials.get_mut(material))
+            {
+                let (_, texture) = parse_texture(&texture);
+
+                material.base_color_texture = Some(textures.add(texture));
+            }
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn apply_wad_textures_system(
+    mut commands: Commands,
+    mut query: Query<(Entity, &BspNeedsWad, &mut Visible)>,
+    mut manager: ResMut<WadManager>,
+    config: Res<BspConfig>,
+    asset_server: Res<AssetServer>,
+    wads: Res<Assets<BspWad>>,
+    mut textures: ResMut<Assets<BevyTexture>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    // Faces wait until every WAD their map declares is loaded
+    let mut ready = HashMap::new();
+
+    for (_, needs_wad, _) in query.iter_mut() {
+        if !ready.contains_key(&needs_wad.wads) {
+            manager.request(&needs_wad.wads, &config.wad_dirs, &asset_server);
+
+            let is_loaded = manager.is_loaded(&needs_wad.wads, &asset_server);
+            if is_loaded {
+                manager.report_missing(&needs_wad.wads, &config.wad_dirs, &wads);
+            }
+
+            ready.insert(needs_wad.wads.clone(), is_loaded);
+        }
+    }
+
+    let mut missing_textures = HashSet::new();
+
+    for (entity, needs_wad, mut visible) in query.iter_mut() {
+        if !ready[&needs_wad.wads] {
+            continue;
+        }
+
+        let mut entity_commands = commands.entity(entity);
+
+        if let Some((is_transparent, material)) = manager.material(
+            &needs_wad.name,
+            &needs_wad.wads,
+            &wads,
+            &mut textures,
+            &mut materials,
+        ) {
+            visible.is_transparent = is_transparent;
+
+            entity_commands.remove::<Handle<StandardMaterial>>();
+            entity_commands.insert(material);
+        } else {
+            missing_textures.insert(needs_wad.name.clone());
+        }
+
+        // No point in trying after first time
+        entity_commands.remove::<BspNeedsWad>();
+    }
+
+    for name in missing_textures {
+        warn!("WAD texture missing: {}", name);
+    }
+}
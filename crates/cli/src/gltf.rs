@@ -0,0 +1,507 @@
+use std::collections::BTreeMap;
+
+use decoder::format::gold_src_30::{FacePolygon, GoldSrc30Bsp};
+use serde_json::{json, Map, Value};
+
+/// Lightmaps are packed in rows of an atlas this wide, in luxels
+const ATLAS_WIDTH: u32 = 1024;
+
+const ARRAY_BUFFER: u32 = 34962;
+const ELEMENT_ARRAY_BUFFER: u32 = 34963;
+const FLOAT: u32 = 5126;
+const UNSIGNED_INT: u32 = 5125;
+
+/// Writes a map as a binary glTF. Models become meshes with a primitive per
+/// texture, lightmaps are packed into one atlas used as the occlusion texture
+/// through the second UV set, and entities become nodes with their keys in
+/// `extras`. Positions stay in map units, turned from Z up to Y up
+pub fn export(bsp: &GoldSrc30Bsp) -> Vec<u8> {
+    let mut gltf = Gltf::default();
+
+    let polygons = (0..bsp.faces.len())
+        .map(|idx| bsp.face_polygon(idx))
+        .collect::<Vec<_>>();
+
+    let atlas = Atlas::pack(&polygons);
+    let lightmap = atlas.as_ref().map(|atlas| {
+        let png = encode_png(atlas.width, atlas.height, png::ColorType::RGB, &atlas.data);
+
+        gltf.texture("lightmaps", png)
+    });
+
+    let mut materials = BTreeMap::new();
+    let mut meshes = vec![];
+
+    for model in bsp.models.iter() {
+        let first_face = model.idx_first_face.max(0) as usize;
+        let faces = first_face..first_face + model.num_faces.max(0) as usize;
+
+        // Faces by texture, each group is drawn as one primitive
+        let mut groups = BTreeMap::<Option<usize>, Vec<usize>>::new();
+
+        for idx in faces {
+            let polygon = match polygons.get(idx) {
+                Some(Some(polygon)) if polygon.positions.len() >= 3 => polygon,
+                _ => continue,
+            };
+
+            let name = polygon
+                .idx_miptex
+                .map(|idx| bsp.textures[idx].name_string())
+                .unwrap_or_default();
+
+            // Sky faces only mark where the sky box shows through
+            if name.eq_ignore_ascii_case("sky") {
+                continue;
+            }
+
+            groups.entry(polygon.idx_miptex).or_default().push(idx);
+        }
+
+        let mut primitives = vec![];
+
+        for (idx_miptex, faces) in groups {
+            let material = *materials
+                .entry(idx_miptex)
+                .or_insert_with(|| gltf.material(bsp, idx_miptex, lightmap));
+
+            let primitive = Primitive::build(bsp, &polygons, &faces, idx_miptex, atlas.as_ref());
+            primitives.push(gltf.primitive(&primitive, material));
+        }
+
+        meshes.push(if primitives.is_empty() {
+            None
+        } else {
+            gltf.meshes.push(json!({ "primitives": primitives }));
+
+            Some(gltf.meshes.len() - 1)
+        });
+    }
+
+    let mut used_models = vec![false; meshes.len()];
+
+    for (idx, entity) in bsp.entities.iter().enumerate() {
+        let name = entity
+            .get("targetname")
+            .or_else(|| entity.classname())
+            .map(str::to_string)
+            .unwrap_or_else(|| format!("entity {}", idx));
+
+        let extras = entity
+            .properties
+            .iter()
+            .map(|(key, value)| (key.clone(), Value::String(value.clone())))
+            .collect::<Map<_, _>>();
+
+        let mut node = json!({ "name": name, "extras": extras });
+
+        if let Some(origin) = entity.origin() {
+            node["translation"] = json!(to_y_up([origin.x, origin.y, origin.z]));
+        }
+
+        if let Some(model) = entity.brush_model().filter(|model| *model < meshes.len()) {
+            used_models[model] = true;
+
+            if let Some(mesh) = meshes[model] {
+                node["mesh"] = json!(mesh);
+            }
+        }
+
+        gltf.nodes.push(node);
+    }
+
+    for (model, mesh) in meshes.iter().enumerate() {
+        if let (false, Some(mesh)) = (used_models[model], mesh) {
+            gltf.nodes
+                .push(json!({ "name": format!("*{}", model), "mesh": mesh }));
+        }
+    }
+
+    gltf.into_glb()
+}
+
+/// Vertices of the faces of one model that share a texture
+#[derive(Default)]
+struct Primitive {
+    positions: Vec<[f32; 3]>,
+    normals: Vec<[f32; 3]>,
+    uvs: Vec<[f32; 2]>,
+    lightmap_uvs: Vec<[f32; 2]>,
+    indices: Vec<u32>,
+}
+
+impl Primitive {
+    fn build(
+        bsp: &GoldSrc30Bsp,
+        polygons: &[Option<FacePolygon>],
+        faces: &[usize],
+        idx_miptex: Option<usize>,
+        atlas: Option<&Atlas>,
+    ) -> Primitive {
+        let mut primitive = Primitive::default();
+
+        let (width, height) = idx_miptex
+            .map(|idx| &bsp.textures[idx])
+            .map(|t| (t.width.max(1) as f32, t.height.max(1) as f32))
+            .unwrap_or((1.0, 1.0));
+
+        for &face in faces {
+            let polygon = polygons[face].as_ref().unwrap();
+            let first = primitive.positions.len() as u32;
+
+            // Faces wind clockwise, glTF front faces are counter-clockwise
+            for idx in (0..polygon.positions.len()).rev() {
+                let position = polygon.positions[idx];
+                let texel = polygon.texels[idx];
+
+                primitive
+                    .positions
+                    .push(to_y_up([position.x, position.y, position.z]));
+                primitive.normals.push(to_y_up([
+                    polygon.normal.x,
+                    polygon.normal.y,
+                    polygon.normal.z,
+                ]));
+                primitive.uvs.push([texel.x / width, texel.y / height]);
+
+                if let Some(atlas) = atlas {
+                    primitive.lightmap_uvs.push(atlas.uv(face, polygon, idx));
+                }
+            }
+
+            for idx in 1..polygon.positions.len() as u32 - 1 {
+                primitive
+                    .indices
+                    .extend_from_slice(&[first, first + idx, first + idx + 1]);
+            }
+        }
+
+        primitive
+    }
+}
+
+/// Lightmaps of every face, packed in rows with a one luxel border so
+/// filtering doesn't bleed between faces
+struct Atlas {
+    width: u32,
+    height: u32,
+    data: Vec<u8>,
+    corners: BTreeMap<usize, (u32, u32)>,
+}
+
+impl Atlas {
+    fn pack(polygons: &[Option<FacePolygon>]) -> Option<Atlas> {
+        let mut faces = polygons
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, polygon)| Some((idx, polygon.as_ref()?.lightmap.as_ref()?)))
+            .filter(|(_, lightmap)| lightmap.width + 2 <= ATLAS_WIDTH)
+            .collect::<Vec<_>>();
+
+        if faces.is_empty() {
+            return None;
+        }
+
+        // Tallest first keeps the rows tight
+        faces.sort_by_key(|(_, lightmap)| std::cmp::Reverse(lightmap.height));
+
+        let mut corners = BTreeMap::new();
+        let (mut x, mut y, mut row_height) = (0, 0, 0);
+
+        for (idx, lightmap) in faces.iter() {
+            if x + lightmap.width + 2 > ATLAS_WIDTH {
+                x = 0;
+                y += row_height;
+                row_height = 0;
+            }
+
+            corners.insert(*idx, (x, y));
+
+            x += lightmap.width + 2;
+            row_height = row_height.max(lightmap.height + 2);
+        }
+
+        let height = y + row_height;
+        let mut data = vec![0; (ATLAS_WIDTH * height * 3) as usize];
+
+        for (idx, lightmap) in faces {
+            let (x, y) = corners[&idx];
+
+            for dy in 0..lightmap.height + 2 {
+                for dx in 0..lightmap.width + 2 {
+                    // Border luxels repeat the nearest edge luxel
+                    let sx = dx.saturating_sub(1).min(lightmap.width - 1);
+                    let sy = dy.saturating_sub(1).min(lightmap.height - 1);
+
+                    let src = ((sy * lightmap.width + sx) * 3) as usize;
+                    let dst = (((y + dy) * ATLAS_WIDTH + x + dx) * 3) as usize;
+
+                    data[dst..dst + 3].copy_from_slice(&lightmap.data[src..src + 3]);
+                }
+            }
+        }
+
+        Some(Atlas {
+            width: ATLAS_WIDTH,
+            height,
+            data,
+            corners,
+        })
+    }
+
+    fn uv(&self, face: usize, polygon: &FacePolygon, idx: usize) -> [f32; 2] {
+        match (self.corners.get(&face), &polygon.lightmap) {
+            (Some(&(x, y)), Some(lightmap)) => {
+                let luxel = lightmap.luxels[idx];
+
+                [
+                    (x as f32 + 1.0 + luxel.x) / self.width as f32,
+                    (y as f32 + 1.0 + luxel.y) / self.height as f32,
+                ]
+            }
+            _ => [0.0, 0.0],
+        }
+    }
+}
+
+#[derive(Default)]
+struct Gltf {
+    buffer: Vec<u8>,
+    buffer_views: Vec<Value>,
+    accessors: Vec<Value>,
+    images: Vec<Value>,
+    textures: Vec<Value>,
+    materials: Vec<Value>,
+    meshes: Vec<Value>,
+    nodes: Vec<Value>,
+}
+
+impl Gltf {
+    fn view(&mut self, data: &[u8], target: Option<u32>) -> usize {
+        pad(&mut self.buffer, 0);
+
+        let mut view = json!({
+            "buffer": 0,
+            "byteOffset": self.buffer.len(),
+            "byteLength": data.len(),
+        });
+
+        if let Some(target) = target {
+            view["target"] = json!(target);
+        }
+
+        self.buffer.extend_from_slice(data);
+        self.buffer_views.push(view);
+
+        self.buffer_views.len() - 1
+    }
+
+    fn accessor<const N: usize>(&mut self, data: &[[f32; N]], bounds: bool) -> usize {
+        let bytes = data
+            .iter()
+            .flatten()
+            .flat_map(|f| f.to_le_bytes())
+            .collect::<Vec<_>>();
+
+        let view = self.view(&bytes, Some(ARRAY_BUFFER));
+
+        let mut accessor = json!({
+            "bufferView": view,
+            "componentType": FLOAT,
+            "count": data.len(),
+            "type": format!("VEC{}", N),
+        });
+
+        // Positions must have bounds
+        if bounds {
+            let mut min = [f32::MAX; N];
+            let mut max = [f32::MIN; N];
+
+            for value in data {
+                for i in 0..N {
+                    min[i] = min[i].min(value[i]);
+                    max[i] = max[i].max(value[i]);
+                }
+            }
+
+            accessor["min"] = json!(min.to_vec());
+            accessor["max"] = json!(max.to_vec());
+        }
+
+        self.accessors.push(accessor);
+
+        self.accessors.len() - 1
+    }
+
+    fn primitive(&mut self, primitive: &Primitive, material: usize) -> Value {
+        let indices = primitive
+            .indices
+            .iter()
+            .flat_map(|i| i.to_le_bytes())
+            .collect::<Vec<_>>();
+
+        let view = self.view(&indices, Some(ELEMENT_ARRAY_BUFFER));
+        self.accessors.push(json!({
+            "bufferView": view,
+            "componentType": UNSIGNED_INT,
+            "count": primitive.indices.len(),
+            "type": "SCALAR",
+        }));
+        let indices = self.accessors.len() - 1;
+
+        let mut attributes = json!({
+            "POSITION": self.accessor(&primitive.positions, true),
+            "NORMAL": self.accessor(&primitive.normals, false),
+            "TEXCOORD_0": self.accessor(&primitive.uvs, false),
+        });
+
+        if !primitive.lightmap_uvs.is_empty() {
+            attributes["TEXCOORD_1"] = json!(self.accessor(&primitive.lightmap_uvs, false));
+        }
+
+        json!({
+            "attributes": attributes,
+            "indices": indices,
+            "material": material,
+        })
+    }
+
+    /// Adds a PNG embedded in the buffer, returning its texture index
+    fn texture(&mut self, name: &str, png: Vec<u8>) -> usize {
+        let view = self.view(&png, None);
+
+        self.images.push(json!({
+            "name": name,
+            "bufferView": view,
+            "mimeType": "image/png",
+        }));
+        self.textures
+            .push(json!({ "source": self.images.len() - 1 }));
+
+        self.textures.len() - 1
+    }
+
+    fn material(
+        &mut self,
+        bsp: &GoldSrc30Bsp,
+        idx_miptex: Option<usize>,
+        lightmap: Option<usize>,
+    ) -> usize {
+        let texture = idx_miptex.map(|idx| &bsp.textures[idx]);
+        let name = texture.map(|t| t.name_string()).unwrap_or_default();
+
+        let mut material = json!({
+            "name": name,
+            "pbrMetallicRoughness": {
+                "metallicFactor": 0.0,
+                "roughnessFactor": 1.0,
+            },
+        });
+
+        // Textures stored in a WAD are left out, the material keeps their name
+        if let Some((texture, rgba)) = texture.and_then(|t| Some((t, t.rgba(0)?))) {
+            let png = encode_png(texture.width, texture.height, png::ColorType::RGBA, &rgba);
+            let index = self.texture(&name, png);
+
+            material["pbrMetallicRoughness"]["baseColorTexture"] = json!({ "index": index });
+        }
+
+        if name.starts_with('{') {
+            material["alphaMode"] = json!("MASK");
+        }
+
+        if let Some(lightmap) = lightmap {
+            material["occlusionTexture"] = json!({ "index": lightmap, "texCoord": 1 });
+        }
+
+        self.materials.push(material);
+
+        self.materials.len() - 1
+    }
+
+    fn into_glb(mut self) -> Vec<u8> {
+        pad(&mut self.buffer, 0);
+
+        let mut root = json!({
+            "asset": {
+                "version": "2.0",
+                "generator": concat!("cli ", env!("CARGO_PKG_VERSION")),
+            },
+            "scene": 0,
+            "scenes": [{ "nodes": (0..self.nodes.len()).collect::<Vec<_>>() }],
+            "nodes": self.nodes,
+            "meshes": self.meshes,
+            "materials": self.materials,
+            "textures": self.textures,
+            "images": self.images,
+            "accessors": self.accessors,
+            "bufferViews": self.buffer_views,
+        });
+
+        if !self.buffer.is_empty() {
+            root["buffers"] = json!([{ "byteLength": self.buffer.len() }]);
+        }
+
+        // Empty arrays aren't valid glTF
+        let root = root
+            .as_object()
+            .into_iter()
+            .flatten()
+            .filter(|(_, value)| value.as_array().map(|a| !a.is_empty()).unwrap_or(true))
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect::<Map<_, _>>();
+
+        let mut json = serde_json::to_vec(&root).unwrap();
+        pad(&mut json, b' ');
+
+        let mut glb = vec![];
+        let length = 12
+            + 8
+            + json.len()
+            + if self.buffer.is_empty() {
+                0
+            } else {
+                8 + self.buffer.len()
+            };
+
+        glb.extend_from_slice(b"glTF");
+        glb.extend_from_slice(&2u32.to_le_bytes());
+        glb.extend_from_slice(&(length as u32).to_le_bytes());
+
+        glb.extend_from_slice(&(json.len() as u32).to_le_bytes());
+        glb.extend_from_slice(b"JSON");
+        glb.extend_from_slice(&json);
+
+        if !self.buffer.is_empty() {
+            glb.extend_from_slice(&(self.buffer.len() as u32).to_le_bytes());
+            glb.extend_from_slice(b"BIN\0");
+            glb.extend_from_slice(&self.buffer);
+        }
+
+        glb
+    }
+}
+
+/// GLB chunks and buffer views start on 4 byte boundaries
+fn pad(data: &mut Vec<u8>, byte: u8) {
+    data.resize(data.len() + (4 - data.len() % 4) % 4, byte);
+}
+
+/// Map space is Z up, glTF is Y up with -Z forward
+fn to_y_up([x, y, z]: [f32; 3]) -> [f32; 3] {
+    [x, z, 0.0 - y]
+}
+
+fn encode_png(width: u32, height: u32, color: png::ColorType, data: &[u8]) -> Vec<u8> {
+    let mut png = vec![];
+
+    let mut encoder = png::Encoder::new(&mut png, width, height);
+    encoder.set_color(color);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder
+        .write_header()
+        .and_then(|mut writer| writer.write_image_data(data))
+        .unwrap();
+
+    png
+}
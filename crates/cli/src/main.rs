@@ -1,10 +1,24 @@
-use std::fs::File;
-use std::io::BufReader;
-use std::path::PathBuf;
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+use std::process;
 
-use decoder::BspFormat;
+use decoder::entity::graph::EntityGraph;
+use decoder::entity::resources;
+use decoder::entity::{self, Entity};
+use decoder::format::gold_src_30::Texture;
+use decoder::format::GoldSrc30Bsp;
+use decoder::tga::Image;
+use decoder::vfs::Vfs;
+use decoder::wad;
+use decoder::{BspEncoder, BspFormat, TgaDecoder, WadDecoder, WadEncoder};
+use serde_json::{json, Map, Value};
 use structopt::StructOpt;
 
+mod gltf;
+mod obj;
+
 fn main() {
     let opts = Opts::from_args();
 
@@ -56,9 +70,599 @@ fn main() {
                 }
             }
         }
+        Subcommand::Graph { path } => {
+            let reader = BufReader::new(File::open(path).unwrap());
+
+            let mut decoder = decoder::BspDecoder::from_reader(reader).unwrap();
+            let bsp = decoder.decode_gold_src_30().unwrap();
+
+            let graph = EntityGraph::build(&bsp.entities);
+
+            for link in graph.dangling() {
+                eprintln!(
+                    "Dangling target `{}` from entity {} ({})",
+                    link.target,
+                    link.from,
+                    bsp.entities[link.from].classname().unwrap_or_default()
+                );
+            }
+
+            for &idx in graph.unreachable.iter() {
+                let entity = &bsp.entities[idx];
+
+                eprintln!(
+                    "Unreachable entity {} ({}) with targetname `{}`",
+                    idx,
+                    entity.classname().unwrap_or_default(),
+                    entity.get("targetname").unwrap_or_default()
+                );
+            }
+
+            print!("{}", graph.to_dot(&bsp.entities));
+        }
+        Subcommand::Entities {
+            command: EntitiesCommand::Export { path, json },
+        } => {
+            let reader = BufReader::new(File::open(path).unwrap());
+
+            let mut decoder = decoder::BspDecoder::from_reader(reader).unwrap();
+            let bsp = decoder.decode_gold_src_30().unwrap();
+
+            for error in bsp.entity_errors.iter() {
+                eprintln!("Entity skipped: {}", error);
+            }
+
+            if json {
+                println!("{}", entities_to_json(&bsp.entities));
+            } else {
+                print!("{}", entity::serialize(&bsp.entities));
+            }
+        }
+        Subcommand::Entities {
+            command: EntitiesCommand::Import { path, entities },
+        } => {
+            let text = fs::read_to_string(&entities).unwrap();
+
+            // JSON exports are an array, entity lump text starts with `{`
+            let parsed = if text.trim_start().starts_with('[') {
+                entities_from_json(&text)
+            } else {
+                entity::parse(&text).map_err(|e| e.to_string())
+            };
+
+            let parsed = parsed.unwrap_or_else(|e| {
+                eprintln!("Can't read entities from {}: {}", entities.display(), e);
+                process::exit(1);
+            });
+
+            // Only the entity lump is rewritten, the rest is copied as it is
+            let result = write_bsp(&path, |writer| {
+                let mut reader = BufReader::new(File::open(&path)?);
+
+                BspEncoder::from_writer(writer).replace_entities_gold_src_30(&mut reader, &parsed)
+            });
+
+            if let Err(e) = result {
+                eprintln!("Can't write {}: {}", path.display(), e);
+                process::exit(1);
+            }
+        }
+        Subcommand::Textures {
+            command:
+                TexturesCommand::Embed {
+                    path,
+                    wads,
+                    strip_wads,
+                },
+        } => {
+            let reader = BufReader::new(File::open(&path).unwrap());
+
+            let mut decoder = decoder::BspDecoder::from_reader(reader).unwrap();
+            let mut bsp = decoder.decode_gold_src_30().unwrap();
+
+            let mut indexes = wads
+                .iter()
+                .map(|wad| {
+                    let reader = BufReader::new(File::open(wad).unwrap());
+
+                    WadDecoder::from_reader(reader).index().unwrap()
+                })
+                .collect::<Vec<_>>();
+
+            for name in bsp.embed_textures(&mut indexes) {
+                eprintln!("Texture `{}` not found in the supplied WADs", name);
+            }
+
+            if strip_wads {
+                let names = wads
+                    .iter()
+                    .filter_map(|wad| wad.file_name())
+                    .map(|name| name.to_string_lossy().to_string())
+                    .collect::<Vec<_>>();
+                let names = names.iter().map(String::as_str).collect::<Vec<_>>();
+
+                wad::strip_declared_wads(&mut bsp.entities, &names);
+            }
+
+            let result = write_bsp(&path, |writer| {
+                BspEncoder::from_writer(writer).encode_gold_src_30(&bsp)
+            });
+
+            if let Err(e) = result {
+                eprintln!("Can't write {}: {}", path.display(), e);
+                process::exit(1);
+            }
+        }
+        Subcommand::Export {
+            format,
+            path,
+            output,
+            axes,
+            scale,
+        } => {
+            let reader = BufReader::new(File::open(&path).unwrap());
+
+            let mut decoder = decoder::BspDecoder::from_reader(reader).unwrap();
+            let bsp = decoder.decode_gold_src_30().unwrap();
+
+            if scale <= 0.0 {
+                eprintln!("The scale must be positive, not {}", scale);
+                process::exit(1);
+            }
+
+            match format {
+                ExportFormat::Gltf => fs::write(&output, gltf::export(&bsp)).unwrap(),
+                ExportFormat::Obj => obj::export(&bsp, &output, axes, scale).unwrap(),
+                ExportFormat::Map => {
+                    let map = decoder::map::decompile(&bsp);
+
+                    fs::write(&output, decoder::map::serialize(&map)).unwrap();
+                }
+            }
+        }
+        Subcommand::Res { path, game_dirs } => {
+            let reader = BufReader::new(File::open(&path).unwrap());
+
+            let mut decoder = decoder::BspDecoder::from_reader(reader).unwrap();
+            let bsp = decoder.decode_gold_src_30().unwrap();
+
+            let name = path.file_stem().unwrap_or_default().to_string_lossy();
+            let mut files = resources::resources(&bsp.entities);
+
+            let mut vfs = Vfs::new();
+            for dir in game_dirs.iter() {
+                vfs.mount_game_dir(dir).unwrap();
+            }
+
+            // Detail textures are listed next to the map, not in its entities
+            let detail = format!("maps/{}_detail.txt", name);
+            let text = if game_dirs.is_empty() {
+                fs::read(path.with_file_name(format!("{}_detail.txt", name))).ok()
+            } else {
+                vfs.read(&detail).ok()
+            };
+
+            if let Some(text) = text {
+                files.push(detail);
+                files.extend(resources::detail_textures(&String::from_utf8_lossy(&text)));
+            }
+
+            if !game_dirs.is_empty() {
+                files.retain(|file| {
+                    let exists = vfs.exists(file);
+
+                    if !exists {
+                        eprintln!("Skipping `{}`, it isn't in the game directories", file);
+                    }
+
+                    exists
+                });
+            }
+
+            let mut res = format!("// {}.res, files maps/{}.bsp needs\n\n", name, name);
+            for file in files.iter() {
+                res.push_str(file);
+                res.push('\n');
+            }
+
+            let res_path = path.with_extension("res");
+            fs::write(&res_path, res).unwrap();
+
+            println!("Wrote {} files to {}", files.len(), res_path.display());
+        }
+        Subcommand::Textures {
+            command: TexturesCommand::List { path },
+        } => {
+            let (textures, bsp) = load_textures(&path);
+
+            let mut faces = BTreeMap::<usize, Vec<usize>>::new();
+
+            if let Some(bsp) = &bsp {
+                for (idx, face) in bsp.faces.iter().enumerate() {
+                    if let Some(info) = bsp.texture_info.get(face.texture_info as usize) {
+                        faces.entry(info.idx_miptex as usize).or_default().push(idx);
+                    }
+                }
+            }
+
+            for (idx, texture) in textures.iter().enumerate() {
+                let size = format!("{}x{}", texture.width, texture.height);
+                // Where a map's texture is stored and which faces use it
+                let (source, used_by) = match (&bsp, faces.get(&idx)) {
+                    (None, _) => ("", String::new()),
+                    (Some(_), faces) => (
+                        if texture.offsets[0] > 0 {
+                            "embedded"
+                        } else {
+                            "wad"
+                        },
+                        faces
+                            .map(|faces| format!("faces {}", ranges(faces)))
+                            .unwrap_or_else(|| "no faces".to_string()),
+                    ),
+                };
+
+                let line = format!(
+                    "{:<16} {:>9} {:<8} {}",
+                    texture.name_string(),
+                    size,
+                    source,
+                    used_by
+                );
+
+                println!("{}", line.trim_end());
+            }
+        }
+        Subcommand::Textures {
+            command: TexturesCommand::Extract { path, output, mips },
+        } => {
+            let (textures, _) = load_textures(&path);
+
+            fs::create_dir_all(&output).unwrap();
+
+            for texture in textures.iter() {
+                let name = texture_file_stem(&texture.name_string());
+
+                if texture.rgba(0).is_none() {
+                    eprintln!("Skipping `{}`, it's stored in a WAD", name);
+                    continue;
+                }
+
+                for level in 0..if mips { 4 } else { 1 } {
+                    let file_name = match level {
+                        0 => format!("{}.png", name),
+                        _ => format!("{}_mip{}.png", name, level),
+                    };
+
+                    let data = texture.rgba(level).unwrap_or_default();
+                    let (width, height) = (texture.width >> level, texture.height >> level);
+
+                    if let Err(e) = save_png(&output.join(&file_name), width, height, &data) {
+                        eprintln!("Can't write {}: {}", file_name, e);
+                    }
+                }
+            }
+        }
+        Subcommand::Textures {
+            command: TexturesCommand::Report { path, wads },
+        } => {
+            let reader = BufReader::new(File::open(&path).unwrap());
+
+            let mut decoder = decoder::BspDecoder::from_reader(reader).unwrap();
+            let bsp = decoder.decode_gold_src_30().unwrap();
+
+            let indexes = wads
+                .iter()
+                .map(|wad| {
+                    let reader = BufReader::new(File::open(wad).unwrap());
+                    let name = wad.file_name().unwrap_or_default().to_string_lossy();
+
+                    (
+                        name.to_string(),
+                        WadDecoder::from_reader(reader).index().unwrap(),
+                    )
+                })
+                .collect::<Vec<_>>();
+
+            // Face count of every texture a texture info points at
+            let mut faces = BTreeMap::<usize, usize>::new();
+
+            for info in bsp.texture_info.iter() {
+                faces.entry(info.idx_miptex as usize).or_default();
+            }
+
+            for face in bsp.faces.iter() {
+                if let Some(info) = bsp.texture_info.get(face.texture_info as usize) {
+                    *faces.entry(info.idx_miptex as usize).or_default() += 1;
+                }
+            }
+
+            let mut missing = vec![];
+
+            let textures = faces
+                .iter()
+                .map(|(&idx, &faces)| {
+                    let texture = match bsp.textures.get(idx) {
+                        Some(texture) => texture,
+                        None => {
+                            return json!({
+                                "index": idx,
+                                "faces": faces,
+                                "status": "invalid",
+                            })
+                        }
+                    };
+
+                    let name = texture.name_string();
+
+                    let wad = indexes
+                        .iter()
+                        .find(|(_, index)| {
+                            matches!(
+                                index.find(&name).map(|entry| entry.kind),
+                                Some(wad::KIND_MIPTEX) | Some(wad::KIND_QUAKE_MIPTEX)
+                            )
+                        })
+                        .map(|(wad, _)| wad.clone());
+
+                    let status = if texture.offsets[0] > 0 {
+                        "embedded"
+                    } else if wad.is_some() {
+                        "wad"
+                    } else {
+                        missing.push(name.clone());
+                        "missing"
+                    };
+
+                    json!({
+                        "index": idx,
+                        "name": name,
+                        "width": texture.width,
+                        "height": texture.height,
+                        "faces": faces,
+                        "status": status,
+                        "wad": if texture.offsets[0] > 0 { None } else { wad },
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            let report = json!({
+                "map": path.to_string_lossy(),
+                "declared_wads": wad::declared_wads(&bsp.entities),
+                "wads": indexes.iter().map(|(wad, _)| wad).collect::<Vec<_>>(),
+                "textures": textures,
+                "missing": missing,
+            });
+
+            println!("{}", serde_json::to_string_pretty(&report).unwrap());
+        }
+        Subcommand::Wad {
+            command: WadCommand::Build { dir, output },
+        } => {
+            let mut paths = fs::read_dir(dir)
+                .unwrap()
+                .flatten()
+                .map(|entry| entry.path())
+                .filter(|path| path.is_file())
+                .collect::<Vec<_>>();
+            paths.sort();
+
+            let mut encoder = WadEncoder::from_writer(File::create(output).unwrap()).unwrap();
+
+            for path in paths {
+                let name = path.file_stem().unwrap_or_default().to_string_lossy();
+
+                let image = match load_image(&path) {
+                    Some(Ok(image)) => image,
+                    Some(Err(e)) => {
+                        eprintln!("Skipping {}: {}", path.display(), e);
+                        continue;
+                    }
+                    None => continue,
+                };
+
+                if let Err(e) = encoder.add_texture(&name, image.width, image.height, &image.data) {
+                    eprintln!("Skipping {}: {}", path.display(), e);
+                }
+            }
+
+            encoder.finish().unwrap();
+        }
+    }
+}
+
+/// Textures of a `.wad`, or of a `.bsp` along with the map
+fn load_textures(path: &Path) -> (Vec<Texture>, Option<GoldSrc30Bsp>) {
+    let reader = BufReader::new(File::open(path).unwrap());
+
+    let is_wad = path
+        .extension()
+        .map(|e| e.eq_ignore_ascii_case("wad"))
+        .unwrap_or_default();
+
+    if is_wad {
+        let wad = WadDecoder::from_reader(reader).decode().unwrap();
+
+        // Directory order, with the WAD palette applied to Quake textures
+        let textures = wad
+            .entries
+            .iter()
+            .filter_map(|entry| wad.textures.get(&entry.dir.name))
+            .cloned()
+            .collect();
+
+        (textures, None)
+    } else {
+        let mut decoder = decoder::BspDecoder::from_reader(reader).unwrap();
+        let bsp = decoder.decode_gold_src_30().unwrap();
+
+        (bsp.textures.clone(), Some(bsp))
+    }
+}
+
+/// Sorted indices as ranges, like `0-3, 7, 9-10`
+fn ranges(indices: &[usize]) -> String {
+    let mut ranges: Vec<(usize, usize)> = vec![];
+
+    for &idx in indices {
+        match ranges.last_mut() {
+            Some((_, end)) if *end + 1 == idx => *end = idx,
+            _ => ranges.push((idx, idx)),
+        }
+    }
+
+    ranges
+        .iter()
+        .map(|&(start, end)| {
+            if start == end {
+                start.to_string()
+            } else {
+                format!("{}-{}", start, end)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Rewrites a .bsp through a temporary file next to it, so a failed write
+/// leaves the original as it was
+fn write_bsp<F>(path: &Path, encode: F) -> decoder::Result<()>
+where
+    F: FnOnce(BufWriter<File>) -> decoder::Result<BufWriter<File>>,
+{
+    let mut temp = path.as_os_str().to_owned();
+    temp.push(".tmp");
+    let temp = PathBuf::from(temp);
+
+    let write = || -> decoder::Result<()> {
+        let writer = encode(BufWriter::new(File::create(&temp)?))?;
+
+        writer
+            .into_inner()
+            .map_err(|e| e.into_error())?
+            .sync_all()?;
+
+        Ok(fs::rename(&temp, path)?)
+    };
+
+    let result = write();
+
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+
+    result
+}
+
+/// A texture name that's safe to use as a file name
+fn texture_file_stem(name: &str) -> String {
+    name.replace(&['<', '>', ':', '"', '/', '\\', '|', '?', '*'][..], "_")
+}
+
+fn save_png(path: &Path, width: u32, height: u32, rgba: &[u8]) -> Result<(), png::EncodingError> {
+    let writer = BufWriter::new(File::create(path)?);
+
+    let mut encoder = png::Encoder::new(writer, width, height);
+    encoder.set_color(png::ColorType::RGBA);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    encoder.write_header()?.write_image_data(rgba)
+}
+
+/// Entities as an array of objects, keeping the order of their keys
+fn entities_to_json(entities: &[Entity]) -> String {
+    let entities = entities
+        .iter()
+        .map(|entity| {
+            let properties = entity
+                .properties
+                .iter()
+                .map(|(key, value)| (key.clone(), Value::String(value.clone())))
+                .collect::<Map<_, _>>();
+
+            Value::Object(properties)
+        })
+        .collect();
+
+    serde_json::to_string_pretty(&Value::Array(entities)).unwrap()
+}
+
+fn entities_from_json(text: &str) -> Result<Vec<Entity>, String> {
+    let value = serde_json::from_str::<Value>(text).map_err(|e| e.to_string())?;
+
+    let entities = value
+        .as_array()
+        .ok_or_else(|| "expected an array of entities".to_string())?;
+
+    entities
+        .iter()
+        .map(|entity| {
+            let properties = entity
+                .as_object()
+                .ok_or_else(|| "expected each entity to be an object".to_string())?;
+
+            let properties = properties
+                .iter()
+                .map(|(key, value)| match value {
+                    Value::String(value) => Ok((key.clone(), value.clone())),
+                    // Numbers are allowed for convenience, the lump stores text
+                    Value::Number(value) => Ok((key.clone(), value.to_string())),
+                    _ => Err(format!("value of `{}` must be a string", key)),
+                })
+                .collect::<Result<_, _>>()?;
+
+            Ok(Entity { properties })
+        })
+        .collect()
+}
+
+/// Reads a `.png` or `.tga` as RGBA8, `None` for other files
+fn load_image(path: &Path) -> Option<Result<Image, String>> {
+    let extension = path.extension()?.to_string_lossy().to_lowercase();
+
+    let file = match File::open(path) {
+        Ok(file) => BufReader::new(file),
+        Err(e) => return Some(Err(e.to_string())),
+    };
+
+    match extension.as_str() {
+        "tga" => Some(
+            TgaDecoder::from_reader(file)
+                .decode()
+                .map_err(|e| e.to_string()),
+        ),
+        "png" => Some(load_png(file).map_err(|e| e.to_string())),
+        _ => None,
     }
 }
 
+fn load_png(file: BufReader<File>) -> Result<Image, png::DecodingError> {
+    let (info, mut reader) = png::Decoder::new(file).read_info()?;
+
+    let mut buf = vec![0; info.buffer_size()];
+    reader.next_frame(&mut buf)?;
+
+    // The decoder expands palettes and 16 bit channels, leaving 8 bit channels
+    let data = match info.color_type {
+        png::ColorType::RGBA => buf,
+        png::ColorType::RGB => buf
+            .chunks_exact(3)
+            .flat_map(|c| [c[0], c[1], c[2], 255])
+            .collect(),
+        png::ColorType::GrayscaleAlpha => buf
+            .chunks_exact(2)
+            .flat_map(|c| [c[0], c[0], c[0], c[1]])
+            .collect(),
+        _ => buf.iter().flat_map(|&c| [c, c, c, 255]).collect(),
+    };
+
+    Ok(Image {
+        width: info.width,
+        height: info.height,
+        data,
+    })
+}
+
 #[derive(Debug, StructOpt)]
 #[structopt()]
 struct Opts {
@@ -73,4 +677,150 @@ enum Subcommand {
         /// Path of the .bsp file
         path: PathBuf,
     },
+    /// Print the entity target / targetname graph of the supplied .bsp file as Graphviz DOT
+    Graph {
+        /// Path of the .bsp file
+        path: PathBuf,
+    },
+    /// Export the geometry, textures, lightmaps and entities of a .bsp file
+    Export {
+        /// Format to write, `gltf` writes a binary .glb, `obj` writes a .obj
+        /// with its .mtl and texture PNGs next to it and `map` decompiles to
+        /// Valve 220 .map source
+        #[structopt(long, default_value = "gltf")]
+        format: ExportFormat,
+        /// Path of the .bsp file
+        path: PathBuf,
+        /// Path of the file to write
+        output: PathBuf,
+        /// Axes of obj positions, `z-up` as in the map, `y-up`, or `bevy` for
+        /// the viewer's Y Z X swizzle. glTF is always Y up
+        #[structopt(long, default_value = "y-up")]
+        axes: obj::Axes,
+        /// Factor obj positions are scaled by, like 0.0254 for meters
+        #[structopt(long, default_value = "1")]
+        scale: f32,
+    },
+    /// Write the .res list of files a .bsp file needs next to it, so servers
+    /// send them to clients
+    Res {
+        /// Path of the .bsp file
+        path: PathBuf,
+        /// Game or mod directories to check the files exist in, like
+        /// `cstrike` then `valve`. Files not found in them are left out
+        #[structopt(long = "game-dir")]
+        game_dirs: Vec<PathBuf>,
+    },
+    /// Export or replace the entities of a .bsp file, without recompiling it
+    Entities {
+        #[structopt(subcommand)]
+        command: EntitiesCommand,
+    },
+    /// Work with the textures of a .bsp file
+    Textures {
+        #[structopt(subcommand)]
+        command: TexturesCommand,
+    },
+    /// Work with .wad texture files
+    Wad {
+        #[structopt(subcommand)]
+        command: WadCommand,
+    },
+}
+
+#[derive(Debug, Clone, Copy)]
+enum ExportFormat {
+    Gltf,
+    Obj,
+    Map,
+}
+
+impl std::str::FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "gltf" | "glb" => Ok(ExportFormat::Gltf),
+            "obj" => Ok(ExportFormat::Obj),
+            "map" => Ok(ExportFormat::Map),
+            _ => Err(format!(
+                "Unknown export format `{}`, expected gltf, obj or map",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Debug, StructOpt)]
+enum EntitiesCommand {
+    /// Print the entity lump as editable text, or as JSON
+    Export {
+        /// Path of the .bsp file
+        path: PathBuf,
+        /// Print an array of JSON objects instead of entity lump text
+        #[structopt(long)]
+        json: bool,
+    },
+    /// Replace the entities of a .bsp file, leaving every other lump as is
+    Import {
+        /// Path of the .bsp file, which is rewritten
+        path: PathBuf,
+        /// Entity lump text or JSON, as printed by `export`
+        entities: PathBuf,
+    },
+}
+
+#[derive(Debug, StructOpt)]
+enum TexturesCommand {
+    /// List the textures of a .bsp or .wad file with their size, whether a map
+    /// embeds them or takes them from a WAD, and the faces using them
+    List {
+        /// Path of the .bsp or .wad file
+        path: PathBuf,
+    },
+    /// Print a JSON report of the textures a .bsp file's texture infos use,
+    /// their face counts and whether each is embedded, in a WAD or missing
+    Report {
+        /// Path of the .bsp file
+        path: PathBuf,
+        /// WADs to look textures up in, searched in order
+        wads: Vec<PathBuf>,
+    },
+    /// Save the textures of a .bsp or .wad file as PNG images. `{` textures
+    /// are transparent where they use their last palette color
+    Extract {
+        /// Path of the .bsp or .wad file
+        path: PathBuf,
+        /// Directory to write the images to
+        #[structopt(short, long)]
+        output: PathBuf,
+        /// Also save mip levels 1 to 3, as `<name>_mip<level>.png`
+        #[structopt(long)]
+        mips: bool,
+    },
+    /// Copy the textures a .bsp file names from WADs into it, so the WADs
+    /// don't need to be shipped
+    Embed {
+        /// Path of the .bsp file, which is rewritten
+        path: PathBuf,
+        /// WADs to take textures from, searched in order
+        #[structopt(required = true)]
+        wads: Vec<PathBuf>,
+        /// Remove the supplied WADs from the worldspawn `wad` key
+        #[structopt(long)]
+        strip_wads: bool,
+    },
+}
+
+#[derive(Debug, StructOpt)]
+enum WadCommand {
+    /// Build a WAD3 from the .png and .tga images in a directory, each named
+    /// after its file. Names starting with `{` get transparency from alpha
+    Build {
+        /// Directory of images, with sizes that are multiples of 16
+        dir: PathBuf,
+        /// Path of the .wad file to write
+        #[structopt(short, long)]
+        output: PathBuf,
+    },
 }
@@ -0,0 +1,180 @@
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+use decoder::format::GoldSrc30Bsp;
+
+/// How map positions, which are Z up, are turned into exported ones
+#[derive(Debug, Clone, Copy)]
+pub enum Axes {
+    /// Positions as they are in the map
+    ZUp,
+    /// Y up with -Z forward, what most OBJ importers expect
+    YUp,
+    /// The `vec3tofloat3` swizzle the viewer uses, Y Z X
+    Bevy,
+}
+
+impl Axes {
+    fn convert(self, [x, y, z]: [f32; 3]) -> [f32; 3] {
+        let converted = match self {
+            Axes::ZUp => [x, y, z],
+            Axes::YUp => [x, z, -y],
+            Axes::Bevy => [y, z, x],
+        };
+
+        // Adding zero writes -0 as 0
+        converted.map(|c| c + 0.0)
+    }
+}
+
+impl FromStr for Axes {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "z-up" => Ok(Axes::ZUp),
+            "y-up" => Ok(Axes::YUp),
+            "bevy" => Ok(Axes::Bevy),
+            _ => Err(format!("Unknown axes `{}`, expected z-up, y-up or bevy", s)),
+        }
+    }
+}
+
+/// Writes a map as a Wavefront .obj with an object per model and a material
+/// group per texture, the .mtl next to it and embedded textures as PNGs
+/// beside both. Every axis option is a rotation, so faces are rewound
+/// counter-clockwise the same way for each
+pub fn export(bsp: &GoldSrc30Bsp, output: &Path, axes: Axes, scale: f32) -> io::Result<()> {
+    let dir = output.parent().unwrap_or_else(|| Path::new(""));
+    let mtl_name = output.with_extension("mtl");
+    let mtl_name = mtl_name.file_name().unwrap_or_default().to_string_lossy();
+
+    let mut obj = String::new();
+    let mut mtl = String::new();
+
+    writeln!(obj, "mtllib {}", mtl_name).unwrap();
+
+    // Positions, UVs and normals are numbered across the whole file
+    let (mut num_positions, mut num_normals) = (0, 0);
+    let mut materials = BTreeMap::new();
+
+    for (idx_model, model) in bsp.models.iter().enumerate() {
+        let first_face = model.idx_first_face.max(0) as usize;
+        let faces = first_face..first_face + model.num_faces.max(0) as usize;
+
+        let mut groups = BTreeMap::<Option<usize>, Vec<_>>::new();
+
+        for idx in faces {
+            let polygon = match bsp.face_polygon(idx) {
+                Some(polygon) if polygon.positions.len() >= 3 => polygon,
+                _ => continue,
+            };
+
+            let name = polygon
+                .idx_miptex
+                .map(|idx| bsp.textures[idx].name_string())
+                .unwrap_or_default();
+
+            // Sky faces only mark where the sky box shows through
+            if name.eq_ignore_ascii_case("sky") {
+                continue;
+            }
+
+            groups.entry(polygon.idx_miptex).or_default().push(polygon);
+        }
+
+        if groups.is_empty() {
+            continue;
+        }
+
+        writeln!(obj, "\no model_{}", idx_model).unwrap();
+
+        // Models of entities with an origin brush are stored around it
+        let origin = bsp
+            .entities
+            .iter()
+            .find(|entity| entity.brush_model() == Some(idx_model))
+            .and_then(|entity| entity.origin())
+            .unwrap_or_default();
+
+        for (idx_miptex, polygons) in groups {
+            let material = materials
+                .entry(idx_miptex)
+                .or_insert_with(|| material(bsp, idx_miptex, dir, &mut mtl))
+                .clone();
+
+            writeln!(obj, "g model_{}_{}", idx_model, material).unwrap();
+            writeln!(obj, "usemtl {}", material).unwrap();
+
+            let (width, height) = idx_miptex
+                .map(|idx| &bsp.textures[idx])
+                .map(|t| (t.width.max(1) as f32, t.height.max(1) as f32))
+                .unwrap_or((1.0, 1.0));
+
+            for polygon in polygons {
+                let normal = polygon.normal;
+                let [x, y, z] = axes.convert([normal.x, normal.y, normal.z]);
+                writeln!(obj, "vn {} {} {}", x, y, z).unwrap();
+                num_normals += 1;
+
+                let mut face = String::from("f");
+
+                // Faces wind clockwise, OBJ front faces are counter-clockwise
+                for idx in (0..polygon.positions.len()).rev() {
+                    let position = (polygon.positions[idx] + origin) * scale;
+                    let texel = polygon.texels[idx];
+
+                    let [x, y, z] = axes.convert([position.x, position.y, position.z]);
+                    writeln!(obj, "v {} {} {}", x, y, z).unwrap();
+                    // OBJ UVs start at the bottom left, textures at the top left
+                    writeln!(obj, "vt {} {}", texel.x / width, 0.0 - texel.y / height).unwrap();
+                    num_positions += 1;
+
+                    write!(face, " {0}/{0}/{1}", num_positions, num_normals).unwrap();
+                }
+
+                writeln!(obj, "{}", face).unwrap();
+            }
+        }
+    }
+
+    fs::write(output, obj)?;
+    fs::write(dir.join(mtl_name.as_ref()), mtl)
+}
+
+/// Adds a material to the .mtl, saving its texture if the map embeds it, and
+/// returns its name
+fn material(bsp: &GoldSrc30Bsp, idx_miptex: Option<usize>, dir: &Path, mtl: &mut String) -> String {
+    let texture = idx_miptex.map(|idx| &bsp.textures[idx]);
+    let name = texture
+        .map(|t| crate::texture_file_stem(&t.name_string()))
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "untextured".to_string());
+
+    writeln!(mtl, "newmtl {}", name).unwrap();
+    writeln!(mtl, "Kd 1 1 1").unwrap();
+
+    // Textures stored in a WAD are left out, the material keeps their name
+    if let Some((texture, rgba)) = texture.and_then(|t| Some((t, t.rgba(0)?))) {
+        let file_name = format!("{}.png", name);
+
+        match crate::save_png(&dir.join(&file_name), texture.width, texture.height, &rgba) {
+            Ok(()) => {
+                writeln!(mtl, "map_Kd {}", file_name).unwrap();
+
+                if name.starts_with('{') {
+                    writeln!(mtl, "map_d {}", file_name).unwrap();
+                }
+            }
+            Err(e) => eprintln!("Can't write {}: {}", file_name, e),
+        }
+    }
+
+    mtl.push('\n');
+
+    name
+}
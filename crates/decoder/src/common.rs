@@ -1,6 +1,6 @@
-use std::io::{Read, Seek};
+use std::io::{Read, Seek, Write};
 
-use byteorder::{LittleEndian, ReadBytesExt};
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 use glam::Vec3;
 
 use crate::Result;
@@ -72,3 +72,47 @@ pub(crate) fn read_vec3<R: Read + Seek>(reader: &mut R) -> Result<Vec3> {
 
     Ok(Vec3::new(x, y, z))
 }
+
+pub(crate) fn write_array_f32<W: Write>(writer: &mut W, array: &[f32]) -> Result<()> {
+    for i in array.iter() {
+        writer.write_f32::<LittleEndian>(*i)?;
+    }
+
+    Ok(())
+}
+
+pub(crate) fn write_array_i32<W: Write>(writer: &mut W, array: &[i32]) -> Result<()> {
+    for i in array.iter() {
+        writer.write_i32::<LittleEndian>(*i)?;
+    }
+
+    Ok(())
+}
+
+pub(crate) fn write_array_i16<W: Write>(writer: &mut W, array: &[i16]) -> Result<()> {
+    for i in array.iter() {
+        writer.write_i16::<LittleEndian>(*i)?;
+    }
+
+    Ok(())
+}
+
+pub(crate) fn write_array_u16<W: Write>(writer: &mut W, array: &[u16]) -> Result<()> {
+    for i in array.iter() {
+        writer.write_u16::<LittleEndian>(*i)?;
+    }
+
+    Ok(())
+}
+
+pub(crate) fn write_array_u32<W: Write>(writer: &mut W, array: &[u32]) -> Result<()> {
+    for i in array.iter() {
+        writer.write_u32::<LittleEndian>(*i)?;
+    }
+
+    Ok(())
+}
+
+pub(crate) fn write_vec3<W: Write>(writer: &mut W, vec: Vec3) -> Result<()> {
+    write_array_f32(writer, &[vec.x, vec.y, vec.z])
+}
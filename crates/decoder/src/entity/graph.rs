@@ -0,0 +1,203 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use super::Entity;
+
+/// Keys of a `multi_manager` that aren't targets
+const MULTI_MANAGER_KEYS: [&str; 5] = ["classname", "targetname", "origin", "spawnflags", "wait"];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LinkKind {
+    Target,
+    KillTarget,
+    MultiManager { delay: f32 },
+}
+
+/// A `target` style reference from one entity to every entity with a matching `targetname`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Link {
+    pub from: usize,
+    pub target: String,
+    pub kind: LinkKind,
+    pub to: Vec<usize>,
+}
+
+/// The input / output graph between entities, nodes are indices into the entity list
+#[derive(Debug, Clone, Default)]
+pub struct EntityGraph {
+    pub links: Vec<Link>,
+    pub unreachable: Vec<usize>,
+}
+
+impl EntityGraph {
+    pub fn build(entities: &[Entity]) -> EntityGraph {
+        let mut targetnames: HashMap<&str, Vec<usize>> = HashMap::new();
+
+        for (idx, entity) in entities.iter().enumerate() {
+            if let Some(name) = entity.get("targetname") {
+                targetnames.entry(name).or_default().push(idx);
+            }
+        }
+
+        let mut links = vec![];
+
+        for (from, entity) in entities.iter().enumerate() {
+            let mut link = |target: &str, kind: LinkKind| {
+                let to = targetnames.get(target).cloned().unwrap_or_default();
+
+                links.push(Link {
+                    from,
+                    target: target.to_string(),
+                    kind,
+                    to,
+                });
+            };
+
+            if let Some(target) = entity.get("target") {
+                link(target, LinkKind::Target);
+            }
+
+            if let Some(target) = entity.get("killtarget") {
+                link(target, LinkKind::KillTarget);
+            }
+
+            if entity.classname() == Some("multi_manager") {
+                for (key, value) in entity.properties.iter() {
+                    if MULTI_MANAGER_KEYS.contains(&key.as_str()) {
+                        continue;
+                    }
+
+                    // Duplicate targets are suffixed with `#n`
+                    let target = key.split('#').next().unwrap_or_default();
+                    let delay = value.trim().parse().unwrap_or_default();
+
+                    link(target, LinkKind::MultiManager { delay });
+                }
+            }
+        }
+
+        let mut targeted = vec![false; entities.len()];
+
+        for link in links.iter() {
+            for &to in link.to.iter() {
+                if to != link.from {
+                    targeted[to] = true;
+                }
+            }
+        }
+
+        let unreachable = entities
+            .iter()
+            .enumerate()
+            .filter(|(idx, entity)| entity.get("targetname").is_some() && !targeted[*idx])
+            .map(|(idx, _)| idx)
+            .collect();
+
+        EntityGraph { links, unreachable }
+    }
+
+    /// Links whose target doesn't match the `targetname` of any entity
+    pub fn dangling(&self) -> impl Iterator<Item = &Link> {
+        self.links.iter().filter(|link| link.to.is_empty())
+    }
+
+    /// Renders the graph in Graphviz DOT format
+    pub fn to_dot(&self, entities: &[Entity]) -> String {
+        let mut nodes = vec![false; entities.len()];
+
+        for link in self.links.iter() {
+            nodes[link.from] = true;
+
+            for &to in link.to.iter() {
+                nodes[to] = true;
+            }
+        }
+
+        for &idx in self.unreachable.iter() {
+            nodes[idx] = true;
+        }
+
+        let mut dot = String::from("digraph entities {\n");
+
+        for (idx, entity) in entities.iter().enumerate() {
+            if !nodes[idx] {
+                continue;
+            }
+
+            let mut label = entity.classname().unwrap_or("?").to_string();
+            if let Some(name) = entity.get("targetname") {
+                label.push('\n');
+                label.push_str(name);
+            }
+
+            let style = if self.unreachable.contains(&idx) {
+                " color=orange"
+            } else {
+                ""
+            };
+
+            let _ = writeln!(dot, "    e{} [label={:?}{}];", idx, label, style);
+        }
+
+        for link in self.links.iter() {
+            let label = match link.kind {
+                LinkKind::Target => "target".to_string(),
+                LinkKind::KillTarget => "killtarget".to_string(),
+                LinkKind::MultiManager { delay } => format!("{}s", delay),
+            };
+
+            if link.to.is_empty() {
+                let _ = writeln!(
+                    dot,
+                    "    {:?} [shape=box style=dashed color=red];",
+                    link.target
+                );
+                let _ = writeln!(
+                    dot,
+                    "    e{} -> {:?} [label={:?} color=red];",
+                    link.from, link.target, label
+                );
+            }
+
+            for to in link.to.iter() {
+                let _ = writeln!(dot, "    e{} -> e{} [label={:?}];", link.from, to, label);
+            }
+        }
+
+        dot.push_str("}\n");
+
+        dot
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::entity::parse;
+
+    #[test]
+    fn test_entity_graph() {
+        let entities = parse(
+            r#"
+            { "classname" "trigger_once" "target" "mm" }
+            { "classname" "multi_manager" "targetname" "mm" "door1" "0.5" "door1#1" "2" "lamp" "1" }
+            { "classname" "func_door" "targetname" "door1" }
+            { "classname" "func_door" "targetname" "orphan" }
+            "#,
+        )
+        .unwrap();
+
+        let graph = EntityGraph::build(&entities);
+
+        assert_eq!(graph.links.len(), 4);
+        assert_eq!(graph.links[1].to, vec![2]);
+        assert_eq!(
+            graph
+                .dangling()
+                .map(|l| l.target.as_str())
+                .collect::<Vec<_>>(),
+            vec!["lamp"]
+        );
+        assert_eq!(graph.unreachable, vec![3]);
+    }
+}
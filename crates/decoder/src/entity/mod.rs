@@ -0,0 +1,219 @@
+use glam::Vec3;
+
+use crate::{Error, Result};
+
+pub mod graph;
+pub mod resources;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Entity {
+    pub properties: Vec<(String, String)>,
+}
+
+impl Entity {
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.properties
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn classname(&self) -> Option<&str> {
+        self.get("classname")
+    }
+
+    /// Index of the brush model this entity uses, worldspawn is always model 0
+    pub fn brush_model(&self) -> Option<usize> {
+        if self.classname() == Some("worldspawn") {
+            return Some(0);
+        }
+
+        self.get("model")
+            .and_then(|model| model.strip_prefix('*'))
+            .and_then(|idx| idx.parse().ok())
+    }
+
+    pub fn origin(&self) -> Option<Vec3> {
+        self.get("origin").and_then(parse_vec3)
+    }
+}
+
+pub(crate) fn parse_vec3(value: &str) -> Option<Vec3> {
+    let mut parts = value.split_whitespace().map(|p| p.parse::<f32>());
+
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(Ok(x)), Some(Ok(y)), Some(Ok(z))) => Some(Vec3::new(x, y, z)),
+        _ => None,
+    }
+}
+
+/// Parses the text of an entity lump into a list of entities, failing on the
+/// first malformed one
+pub fn parse(text: &str) -> Result<Vec<Entity>> {
+    let (entities, errors) = parse_lossy(text);
+
+    match errors.into_iter().next() {
+        Some(error) => Err(error),
+        None => Ok(entities),
+    }
+}
+
+/// Parses the text of an entity lump like the engine does, skipping the
+/// entities it can't read, and returns them with the problems found
+pub fn parse_lossy(text: &str) -> (Vec<Entity>, Vec<Error>) {
+    let mut entities = vec![];
+    let mut errors = vec![];
+    let mut chars = text.chars().peekable();
+    let mut current: Option<Entity> = None;
+
+    let parse_err = |msg: &str| Error::Custom(format!("Invalid entity lump: {}", msg));
+
+    while let Some(c) = chars.next() {
+        let result = match c {
+            '{' => {
+                // The unterminated entity is dropped, this one still reads
+                if current.replace(Entity::default()).is_some() {
+                    errors.push(parse_err("unexpected `{`"));
+                }
+
+                Ok(())
+            }
+            '}' => match current.take() {
+                Some(entity) => {
+                    entities.push(entity);
+                    Ok(())
+                }
+                None => Err(parse_err("unexpected `}`")),
+            },
+            '"' => match (current.as_mut(), read_quoted(&mut chars)) {
+                (None, _) => Err(parse_err("key outside of entity")),
+                (_, None) => Err(parse_err("unterminated key")),
+                (Some(entity), Some(key)) => {
+                    while chars.peek().map(|c| c.is_whitespace()).unwrap_or_default() {
+                        chars.next();
+                    }
+
+                    if chars.peek() != Some(&'"') {
+                        Err(parse_err(&format!("missing value for key `{}`", key)))
+                    } else {
+                        chars.next();
+
+                        match read_quoted(&mut chars) {
+                            Some(value) => {
+                                entity.properties.push((key, value));
+                                Ok(())
+                            }
+                            None => Err(parse_err("unterminated value")),
+                        }
+                    }
+                }
+            },
+            '\0' => break,
+            c if c.is_whitespace() => Ok(()),
+            c => Err(parse_err(&format!("unexpected character `{}`", c))),
+        };
+
+        if let Err(error) = result {
+            errors.push(error);
+
+            // The rest of a malformed entity is skipped up to its closing
+            // brace, stray text up to the next entity
+            if current.take().is_some() {
+                for c in chars.by_ref() {
+                    if c == '}' {
+                        break;
+                    }
+                }
+            } else {
+                while chars.peek().map(|c| *c != '{').unwrap_or_default() {
+                    chars.next();
+                }
+            }
+        }
+    }
+
+    if current.is_some() {
+        errors.push(parse_err("unterminated entity"));
+    }
+
+    (entities, errors)
+}
+
+/// Writes entities back to entity lump text the way the compile tools do,
+/// without the trailing null byte
+pub fn serialize(entities: &[Entity]) -> String {
+    let mut text = String::new();
+
+    for entity in entities {
+        text.push_str("{\n");
+
+        for (key, value) in entity.properties.iter() {
+            text.push_str(&format!("\"{}\" \"{}\"\n", key, value));
+        }
+
+        text.push_str("}\n");
+    }
+
+    text
+}
+
+fn read_quoted(chars: &mut impl Iterator<Item = char>) -> Option<String> {
+    let mut string = String::new();
+
+    for c in chars {
+        if c == '"' {
+            return Some(string);
+        }
+
+        string.push(c);
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_entities() {
+        let text = "{\n\"classname\" \"worldspawn\"\n\"wad\" \"halflife.wad\"\n}\n{\n\"model\" \"*3\"\n\"classname\" \"func_door\"\n}\n\0";
+
+        let entities = parse(text).unwrap();
+
+        assert_eq!(entities.len(), 2);
+        assert_eq!(entities[0].get("wad"), Some("halflife.wad"));
+        assert_eq!(entities[0].brush_model(), Some(0));
+        assert_eq!(entities[1].classname(), Some("func_door"));
+        assert_eq!(entities[1].brush_model(), Some(3));
+
+        assert_eq!(format!("{}\0", serialize(&entities)), text);
+    }
+
+    #[test]
+    fn test_parse_lossy_entities() {
+        let text = "{\n\"classname\" \"worldspawn\"\n}\n\
+            {\n\"classname\" \"light\"\n\"_light\" }\n\
+            stray\n\
+            {\n\"classname\" \"info_player_start\"\n}\n\
+            {\n\"classname\" \"func_wall\n}\n";
+
+        assert!(parse(text).is_err());
+
+        let (entities, errors) = parse_lossy(text);
+        let classnames = entities.iter().map(Entity::classname).collect::<Vec<_>>();
+
+        assert_eq!(
+            classnames,
+            vec![Some("worldspawn"), Some("info_player_start")]
+        );
+        assert_eq!(
+            errors.iter().map(ToString::to_string).collect::<Vec<_>>(),
+            vec![
+                "Invalid entity lump: missing value for key `_light`",
+                "Invalid entity lump: unexpected character `s`",
+                "Invalid entity lump: unterminated value",
+            ]
+        );
+    }
+}
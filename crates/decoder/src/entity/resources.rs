@@ -0,0 +1,105 @@
+use super::Entity;
+use crate::wad::declared_wads;
+
+/// Suffix of each of the six sky images
+const SKY_SIDES: [&str; 6] = ["up", "dn", "lf", "rt", "ft", "bk"];
+
+/// Files a map needs besides itself, as paths relative to the game directory:
+/// its WADs, sky images, models, sprites and sounds. Paths are listed once, in
+/// the order they're first referenced
+pub fn resources(entities: &[Entity]) -> Vec<String> {
+    let mut resources: Vec<String> = vec![];
+
+    let mut add = |path: String| {
+        let path = path.replace('\\', "/");
+
+        if !resources.iter().any(|r| r.eq_ignore_ascii_case(&path)) {
+            resources.push(path);
+        }
+    };
+
+    for wad in declared_wads(entities) {
+        add(wad);
+    }
+
+    for entity in entities {
+        for (key, value) in entity.properties.iter() {
+            let value = value.trim();
+            let lower = value.to_lowercase();
+
+            match key.as_str() {
+                "skyname" if !value.is_empty() => {
+                    for side in SKY_SIDES.iter() {
+                        add(format!("gfx/env/{}{}.tga", value, side));
+                    }
+                }
+                "model" | "sprite" if lower.ends_with(".mdl") || lower.ends_with(".spr") => {
+                    add(value.to_string());
+                }
+                // `message` is only a sound when it names a file, like on `ambient_generic`
+                key if (key == "message" || key.starts_with("noise"))
+                    && lower.ends_with(".wav") =>
+                {
+                    add(format!("sound/{}", value));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    resources
+}
+
+/// Detail textures named by a `maps/<map>_detail.txt`, whose lines hold a
+/// texture, its detail texture and the detail scales
+pub fn detail_textures(text: &str) -> Vec<String> {
+    text.lines()
+        .filter(|line| !line.trim_start().starts_with("//"))
+        .filter_map(|line| line.split_whitespace().nth(1))
+        .map(|detail| format!("gfx/{}.tga", detail.replace('\\', "/")))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::entity::parse;
+
+    #[test]
+    fn test_resources() {
+        let entities = parse(
+            r#"
+            { "classname" "worldspawn" "wad" "\valve\halflife.wad;custom.wad" "skyname" "desert" }
+            { "classname" "ambient_generic" "message" "ambience\wind1.wav" }
+            { "classname" "env_message" "message" "GAMETITLE" }
+            { "classname" "func_door" "model" "*2" "noise1" "doors/doormove1.wav" }
+            { "classname" "env_sprite" "model" "sprites/glow01.spr" }
+            { "classname" "env_sprite" "model" "SPRITES/GLOW01.SPR" }
+            { "classname" "cycler" "model" "models/scientist.mdl" }
+            "#,
+        )
+        .unwrap();
+
+        let resources = resources(&entities);
+
+        assert_eq!(resources.len(), 12);
+        assert_eq!(
+            &resources[..3],
+            ["halflife.wad", "custom.wad", "gfx/env/desertup.tga"]
+        );
+        assert_eq!(
+            &resources[8..],
+            [
+                "sound/ambience/wind1.wav",
+                "sound/doors/doormove1.wav",
+                "sprites/glow01.spr",
+                "models/scientist.mdl"
+            ]
+        );
+
+        assert_eq!(
+            detail_textures("// comment\nrock detail/rock_detail 2.0 2.0\n\n"),
+            vec!["gfx/detail/rock_detail.tga"]
+        );
+    }
+}
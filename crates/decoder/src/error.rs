@@ -12,6 +12,18 @@ pub enum Error {
     InvalidBspFormat { version: BspVersion },
     #[error("Invalid Wad format, must be `WAD2` or `WAD3`")]
     InvalidWadFormat,
+    #[error("Invalid Wad lump: {0}")]
+    InvalidWadLump(&'static str),
+    #[error("Invalid Pak format, must start with `PACK`")]
+    InvalidPakFormat,
+    #[error("Invalid or unsupported Tga image: {0}")]
+    InvalidTgaFormat(&'static str),
+    #[error("Invalid map at line {line}, column {column}: {message}")]
+    InvalidMap {
+        line: usize,
+        column: usize,
+        message: String,
+    },
     #[error(transparent)]
     Io(#[from] std::io::Error),
 }
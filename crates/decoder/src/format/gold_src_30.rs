@@ -2,24 +2,28 @@
 
 use std::convert::TryFrom;
 use std::fmt;
-use std::io::{Read, Seek, SeekFrom};
+use std::io::{Read, Seek, SeekFrom, Write};
 use std::mem::size_of;
 
-use byteorder::{LittleEndian, ReadBytesExt};
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 
 use crate::common::{
     read_array_f32, read_array_i16, read_array_i32, read_array_u16, read_array_u32, read_array_u8,
-    read_vec3,
+    read_vec3, write_array_f32, write_array_i16, write_array_i32, write_array_u16, write_array_u32,
+    write_vec3,
 };
-use crate::{ByteDecoder, Error, Result};
+use crate::entity::{self, Entity};
+use crate::wad::WadIndex;
+use crate::{ByteDecoder, ByteEncoder, Error, Result};
 
 const NUM_LUMPS: usize = 16;
 const MAX_MAP_HULLS: usize = 4;
 pub const MAXTEXTURENAME: usize = 16;
-const MIPLEVELS: usize = 4;
+pub(crate) const MIPLEVELS: usize = 4;
 
 #[derive(Clone)]
 pub struct GoldSrc30Bsp {
+    pub entities: Vec<Entity>,
     pub models: Vec<Model>,
     pub planes: Vec<Plane>,
     pub textures: Vec<Texture>,
@@ -28,16 +32,32 @@ pub struct GoldSrc30Bsp {
     pub lighting: Vec<Lighting>,
     pub vertices: Vec<Vertex>,
     pub nodes: Vec<Node>,
+    pub clip_nodes: Vec<ClipNode>,
     pub leaves: Vec<Leaf>,
     pub mark_surfaces: Vec<MarkSurface>,
     pub visibility: Vec<Visibility>,
     pub texture_info: Vec<TextureInfo>,
     pub faces: Vec<Face>,
+    /// Problems in the entity lump text, the entities they're in are left out
+    /// of `entities`
+    pub entity_errors: Vec<String>,
+    pub(crate) original_lumps: Vec<OriginalLump>,
+}
+
+/// Lump bytes the encoder wouldn't write back the same, like a partial color
+/// at the end of the lighting or a miptex entry that can't be read. They're
+/// written as they were while the lump still encodes to `decoded`
+#[derive(Debug, Clone)]
+pub(crate) struct OriginalLump {
+    lump_type: LumpType,
+    decoded: Vec<u8>,
+    bytes: Vec<u8>,
 }
 
 impl fmt::Debug for GoldSrc30Bsp {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("GoldSrc30Bsp")
+            .field("entities", &format!("{} entities", self.entities.len()))
             .field("models", &format!("{} models", self.models.len()))
             .field("planes", &format!("{} planes", self.planes.len()))
             .field("edges", &format!("{} edges", self.edges.len()))
@@ -48,6 +68,10 @@ impl fmt::Debug for GoldSrc30Bsp {
             .field("lighting", &format!("{} lighting", self.lighting.len()))
             .field("vertices", &format!("{} verices", self.vertices.len()))
             .field("nodes", &format!("{} nodes", self.nodes.len()))
+            .field(
+                "clip_nodes",
+                &format!("{} clip_nodes", self.clip_nodes.len()),
+            )
             .field("leaves", &format!("{} leaves", self.leaves.len()))
             .field(
                 "visibility",
@@ -62,12 +86,151 @@ impl fmt::Debug for GoldSrc30Bsp {
     }
 }
 
+impl GoldSrc30Bsp {
+    pub fn texture_sequences(&self) -> Vec<TextureSequence> {
+        texture_sequences(&self.textures)
+    }
+
+    /// A face as a polygon in map space, with its texture and lightmap
+    /// coordinates. `None` if it points at planes or edges that don't exist
+    pub fn face_polygon(&self, idx: usize) -> Option<FacePolygon> {
+        let face = self.faces.get(idx)?;
+        let plane = self.planes.get(face.plane as usize)?;
+
+        let normal = if face.plane_side > 0 {
+            -plane.normal
+        } else {
+            plane.normal
+        };
+
+        let mut positions = Vec::with_capacity(face.edges as usize);
+        let first_edge = face.first_edge as usize;
+
+        for surf_edge in self
+            .surf_edges
+            .get(first_edge..first_edge + face.edges as usize)?
+        {
+            let edge = self.edges.get(surf_edge.0.unsigned_abs() as usize)?;
+            let vertex = if surf_edge.0 < 0 {
+                edge.vertex[1]
+            } else {
+                edge.vertex[0]
+            };
+
+            positions.push(self.vertices.get(vertex as usize)?.0);
+        }
+
+        let tex_info = self.texture_info.get(face.texture_info as usize);
+        let idx_miptex = tex_info
+            .map(|info| info.idx_miptex as usize)
+            .filter(|idx| *idx < self.textures.len());
+
+        let texels = positions
+            .iter()
+            .map(|position| match tex_info {
+                Some(info) => glam::Vec2::new(
+                    position.dot(info.s_vector) + info.s_shift,
+                    position.dot(info.t_vector) + info.t_shift,
+                ),
+                None => glam::Vec2::ZERO,
+            })
+            .collect::<Vec<_>>();
+
+        let lightmap = self.face_lightmap(face, &texels);
+
+        Some(FacePolygon {
+            positions,
+            normal,
+            idx_miptex,
+            texels,
+            lightmap,
+        })
+    }
+
+    /// Lightmaps have a luxel every 16 texels, covering the face's texture
+    /// extents like the engine's `CalcSurfaceExtents`
+    fn face_lightmap(&self, face: &Face, texels: &[glam::Vec2]) -> Option<FaceLightmap> {
+        if face.styles[0] == 255 || face.lightmap_offset == u32::MAX || texels.is_empty() {
+            return None;
+        }
+
+        let min = texels
+            .iter()
+            .fold(glam::Vec2::splat(f32::MAX), |a, b| a.min(*b));
+        let max = texels
+            .iter()
+            .fold(glam::Vec2::splat(f32::MIN), |a, b| a.max(*b));
+
+        let mins = (min / 16.0).floor();
+        let maxs = (max / 16.0).ceil();
+
+        let width = (maxs.x - mins.x) as u32 + 1;
+        let height = (maxs.y - mins.y) as u32 + 1;
+
+        let first = face.lightmap_offset as usize / 3;
+        let data = self
+            .lighting
+            .get(first..first + (width * height) as usize)?
+            .iter()
+            .flat_map(|luxel| [luxel.r, luxel.g, luxel.b])
+            .collect();
+
+        let luxels = texels
+            .iter()
+            .map(|texel| *texel / 16.0 - mins + glam::Vec2::splat(0.5))
+            .collect();
+
+        Some(FaceLightmap {
+            width,
+            height,
+            data,
+            luxels,
+        })
+    }
+
+    /// Copies textures the map only names into its texture lump, looking them
+    /// up in `wads` in order, like the `-wadinclude` compile option. Returns
+    /// the names that weren't found, or were found with a different size
+    pub fn embed_textures<R: Read + Seek>(&mut self, wads: &mut [WadIndex<R>]) -> Vec<String> {
+        let mut missing = vec![];
+
+        for texture in self
+            .textures
+            .iter_mut()
+            .filter(|t| t.offsets[0] == 0 && !t.is_placeholder())
+        {
+            let name = texture.name_string();
+
+            let found = wads
+                .iter_mut()
+                .find_map(|wad| wad.texture(&name))
+                .filter(|found| (found.width, found.height) == (texture.width, texture.height));
+
+            match found {
+                Some(found) => {
+                    // Keeps the name as the map spells it
+                    *texture = Texture {
+                        name: texture.name,
+                        offsets: mip_offsets(found.width, found.height),
+                        ..found
+                    };
+                }
+                None => missing.push(name),
+            }
+        }
+
+        missing
+    }
+}
+
 pub(crate) fn decode<R: Read + Seek>(reader: &mut R, ident: i32) -> Result<GoldSrc30Bsp> {
     let header = decode_header(reader, ident)?;
+    let (entities, entity_errors) = decode_entities(reader, &header)?;
     let planes = decode_lump::<Plane, R>(reader, &header, LumpType::Planes)?;
     let vertices = decode_lump::<Vertex, R>(reader, &header, LumpType::Vertices)?;
     let visibility = decode_lump::<Visibility, R>(reader, &header, LumpType::Visibility)?;
     let nodes = decode_lump::<Node, R>(reader, &header, LumpType::Nodes)?;
+    let clip_nodes = decode_lump::<ClipNode, R>(reader, &header, LumpType::Clipnodes)?;
     let texture_info = decode_lump::<TextureInfo, R>(reader, &header, LumpType::Texinfo)?;
     let faces = decode_lump::<Face, R>(reader, &header, LumpType::Faces)?;
     let lighting = decode_lump::<Lighting, R>(reader, &header, LumpType::Lighting)?;
@@ -79,7 +242,8 @@ pub(crate) fn decode<R: Read + Seek>(reader: &mut R, ident: i32) -> Result<GoldS
 
     let textures = decode_textures(reader, &header)?;
 
-    Ok(GoldSrc30Bsp {
+    let mut bsp = GoldSrc30Bsp {
+        entities,
         models,
         planes,
         textures,
@@ -88,12 +252,198 @@ pub(crate) fn decode<R: Read + Seek>(reader: &mut R, ident: i32) -> Result<GoldS
         lighting,
         vertices,
         nodes,
+        clip_nodes,
         leaves,
         mark_surfaces,
         visibility,
         texture_info,
         faces,
-    })
+        entity_errors,
+        original_lumps: vec![],
+    };
+
+    for lump_type in LUMP_ORDER.iter().copied() {
+        let lump = header.lumps[lump_type as usize];
+        reader.seek(SeekFrom::Start(lump.file_offset as u64))?;
+
+        let mut bytes = vec![];
+        (&mut *reader)
+            .take(lump.len.max(0) as u64)
+            .read_to_end(&mut bytes)?;
+
+        let decoded = encode_lump(&bsp, lump_type)?;
+
+        if decoded != bytes {
+            bsp.original_lumps.push(OriginalLump {
+                lump_type,
+                decoded,
+                bytes,
+            });
+        }
+    }
+
+    Ok(bsp)
+}
+
+/// Writes every lump in the order the compile tools do, each starting on a
+/// 4 byte boundary, so an unmodified map round trips to the same lumps
+pub(crate) fn encode<W: Write + Seek>(writer: &mut W, bsp: &GoldSrc30Bsp) -> Result<()> {
+    let mut lumps = Vec::with_capacity(LUMP_ORDER.len());
+
+    for lump_type in LUMP_ORDER.iter().copied() {
+        let mut data = encode_lump(bsp, lump_type)?;
+
+        if let Some(original) = bsp
+            .original_lumps
+            .iter()
+            .find(|original| original.lump_type == lump_type && original.decoded == data)
+        {
+            data = original.bytes.clone();
+        }
+
+        lumps.push((lump_type, data));
+    }
+
+    write_lumps(writer, lumps)
+}
+
+/// Copies a map with only its entity lump replaced, the other lumps keep
+/// their order and bytes
+pub(crate) fn replace_entities<R: Read + Seek, W: Write + Seek>(
+    reader: &mut R,
+    writer: &mut W,
+    entities: &[Entity],
+) -> Result<()> {
+    let start = reader.stream_position()?;
+    let ident = reader.read_i32::<LittleEndian>()?;
+
+    if ident != 30 {
+        return Err(Error::InvalidOrUnimplementedIdent { ident });
+    }
+
+    let header = decode_header(reader, ident)?;
+
+    let mut lump_types = LUMP_ORDER.to_vec();
+    lump_types.sort_by_key(|lump_type| header.lumps[*lump_type as usize].file_offset);
+
+    let mut lumps = Vec::with_capacity(lump_types.len());
+
+    for lump_type in lump_types {
+        let data = if lump_type == LumpType::Entities {
+            encode_entities(entities)
+        } else {
+            let lump = header.lumps[lump_type as usize];
+            reader.seek(SeekFrom::Start(start + lump.file_offset.max(0) as u64))?;
+
+            let mut data = vec![];
+            (&mut *reader)
+                .take(lump.len.max(0) as u64)
+                .read_to_end(&mut data)?;
+
+            data
+        };
+
+        lumps.push((lump_type, data));
+    }
+
+    write_lumps(writer, lumps)
+}
+
+/// Writes the header and lumps in the order given, each starting on a 4 byte
+/// boundary
+fn write_lumps<W: Write + Seek>(writer: &mut W, lumps: Vec<(LumpType, Vec<u8>)>) -> Result<()> {
+    let start = writer.stream_position()?;
+
+    let mut header = Header {
+        ident: 30,
+        lumps: [Default::default(); NUM_LUMPS],
+    };
+
+    // Lump locations are filled in once they're written
+    header.encode(writer)?;
+
+    for (lump_type, data) in lumps {
+        let file_offset = writer.stream_position()? - start;
+
+        writer.write_all(&data)?;
+        writer.write_all(&[0; 3][..(4 - data.len() % 4) % 4])?;
+
+        header.lumps[lump_type as usize] = HeaderLump {
+            file_offset: file_offset as i32,
+            len: data.len() as i32,
+        };
+    }
+
+    let end = writer.stream_position()?;
+
+    writer.seek(SeekFrom::Start(start))?;
+    header.encode(writer)?;
+    writer.seek(SeekFrom::Start(end))?;
+
+    Ok(())
+}
+
+fn encode_lump(bsp: &GoldSrc30Bsp, lump_type: LumpType) -> Result<Vec<u8>> {
+    let mut data = vec![];
+
+    match lump_type {
+        LumpType::Entities => data = encode_entities(&bsp.entities),
+        LumpType::Planes => encode_items(&mut data, &bsp.planes)?,
+        LumpType::Textures => encode_textures(&mut data, &bsp.textures)?,
+        LumpType::Vertices => encode_items(&mut data, &bsp.vertices)?,
+        LumpType::Visibility => encode_items(&mut data, &bsp.visibility)?,
+        LumpType::Nodes => encode_items(&mut data, &bsp.nodes)?,
+        LumpType::Texinfo => encode_items(&mut data, &bsp.texture_info)?,
+        LumpType::Faces => encode_items(&mut data, &bsp.faces)?,
+        LumpType::Lighting => encode_items(&mut data, &bsp.lighting)?,
+        LumpType::Clipnodes => encode_items(&mut data, &bsp.clip_nodes)?,
+        LumpType::Leaves => encode_items(&mut data, &bsp.leaves)?,
+        LumpType::Marksurfaces => encode_items(&mut data, &bsp.mark_surfaces)?,
+        LumpType::Edges => encode_items(&mut data, &bsp.edges)?,
+        LumpType::Surfedges => encode_items(&mut data, &bsp.surf_edges)?,
+        LumpType::Models => encode_items(&mut data, &bsp.models)?,
+        LumpType::HeaderLumps => {}
+    }
+
+    Ok(data)
+}
+
+/// Entity lump text, null terminated like the compile tools write it
+fn encode_entities(entities: &[Entity]) -> Vec<u8> {
+    let mut data = entity::serialize(entities).into_bytes();
+    data.push(0);
+
+    data
+}
+
+fn encode_items<L: ByteEncoder, W: Write>(writer: &mut W, items: &[L]) -> Result<()> {
+    for item in items {
+        item.encode(writer)?;
+    }
+
+    Ok(())
+}
+
+/// Miptex lump, a count and offsets then the textures back to back
+fn encode_textures<W: Write>(writer: &mut W, textures: &[Texture]) -> Result<()> {
+    let mut data = vec![];
+    let mut offsets = Vec::with_capacity(textures.len());
+
+    for texture in textures {
+        if texture.is_placeholder() {
+            offsets.push(u32::MAX);
+            continue;
+        }
+
+        offsets.push((4 + textures.len() * 4 + data.len()) as u32);
+        texture.encode(&mut data)?;
+    }
+
+    writer.write_u32::<LittleEndian>(textures.len() as u32)?;
+    write_array_u32(writer, &offsets)?;
+    writer.write_all(&data)?;
+
+    Ok(())
 }
 
 fn decode_header<R: Read + Seek>(reader: &mut R, ident: i32) -> Result<Header> {
@@ -132,6 +482,24 @@ fn decode_lump<L: ByteDecoder, R: Read + Seek>(
     Ok(items)
 }
 
+/// Entities that can be read and the problems with the rest, so maps with a
+/// hand edited or malformed entity lump still load
+fn decode_entities<R: Read + Seek>(
+    reader: &mut R,
+    header: &Header,
+) -> Result<(Vec<Entity>, Vec<String>)> {
+    let lump = header.lumps[LumpType::Entities as usize];
+
+    reader.seek(SeekFrom::Start(lump.file_offset as u64))?;
+
+    let mut bytes = vec![0; lump.len as usize];
+    reader.read_exact(&mut bytes)?;
+
+    let (entities, errors) = entity::parse_lossy(&String::from_utf8_lossy(&bytes));
+
+    Ok((entities, errors.iter().map(ToString::to_string).collect()))
+}
+
 fn decode_textures<R: Read + Seek>(reader: &mut R, header: &Header) -> Result<Vec<Texture>> {
     let lump = header.lumps[LumpType::Textures as usize];
 
@@ -148,11 +516,17 @@ fn decode_textures<R: Read + Seek>(reader: &mut R, header: &Header) -> Result<Ve
     let mut textures = Vec::with_capacity(num_textures);
 
     for offset in offsets {
+        // Unused entries have an offset of -1, they and any entry that can't
+        // be read keep their slot so texture infos still index the right one
+        if offset == u32::MAX as usize {
+            textures.push(Texture::placeholder());
+            continue;
+        }
+
         reader.seek(SeekFrom::Start((lump.file_offset as usize + offset) as u64))?;
 
-        if let Ok(texture) = Texture::decode(reader, lump.file_offset as usize + offset) {
-            textures.push(texture);
-        }
+        let texture = Texture::decode(reader, lump.file_offset as usize + offset);
+        textures.push(texture.unwrap_or_else(|_| Texture::placeholder()));
     }
 
     Ok(textures)
@@ -164,6 +538,20 @@ struct Header {
     lumps: [HeaderLump; NUM_LUMPS],
 }
 
+impl ByteEncoder for Header {
+    fn encode<W: Write>(&self, writer: &mut W) -> Result<()> {
+        writer.write_i32::<LittleEndian>(self.ident)?;
+
+        // The last slot only counts the lumps, it isn't in the file
+        for lump in self.lumps.iter().take(LumpType::HeaderLumps as usize) {
+            writer.write_i32::<LittleEndian>(lump.file_offset)?;
+            writer.write_i32::<LittleEndian>(lump.len)?;
+        }
+
+        Ok(())
+    }
+}
+
 #[derive(Debug, Clone, Copy, Default)]
 struct HeaderLump {
     file_offset: i32,
@@ -171,7 +559,7 @@ struct HeaderLump {
 }
 
 #[repr(usize)]
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 enum LumpType {
     Entities,
     Planes,
@@ -191,6 +579,24 @@ enum LumpType {
     HeaderLumps,
 }
 
+const LUMP_ORDER: [LumpType; NUM_LUMPS - 1] = [
+    LumpType::Planes,
+    LumpType::Leaves,
+    LumpType::Vertices,
+    LumpType::Nodes,
+    LumpType::Texinfo,
+    LumpType::Faces,
+    LumpType::Clipnodes,
+    LumpType::Marksurfaces,
+    LumpType::Surfedges,
+    LumpType::Edges,
+    LumpType::Models,
+    LumpType::Lighting,
+    LumpType::Visibility,
+    LumpType::Entities,
+    LumpType::Textures,
+];
+
 #[derive(Debug, Clone, Copy)]
 pub struct Model {
     pub mins: [f32; 3],
@@ -218,6 +624,20 @@ impl ByteDecoder for Model {
     }
 }
 
+impl ByteEncoder for Model {
+    fn encode<W: Write>(&self, writer: &mut W) -> Result<()> {
+        write_array_f32(writer, &self.mins)?;
+        write_array_f32(writer, &self.maxs)?;
+        write_vec3(writer, self.origin)?;
+        write_array_i32(writer, &self.idx_head_nodes)?;
+        writer.write_i32::<LittleEndian>(self.num_vis_leafs)?;
+        writer.write_i32::<LittleEndian>(self.idx_first_face)?;
+        writer.write_i32::<LittleEndian>(self.num_faces)?;
+
+        Ok(())
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 pub struct Plane {
     pub normal: glam::Vec3,
@@ -251,6 +671,19 @@ impl TryFrom<i32> for PlaneType {
     }
 }
 
+impl From<PlaneType> for i32 {
+    fn from(value: PlaneType) -> i32 {
+        match value {
+            PlaneType::X => 0,
+            PlaneType::Y => 1,
+            PlaneType::Z => 2,
+            PlaneType::AnyX => 3,
+            PlaneType::AnyY => 4,
+            PlaneType::AnyZ => 5,
+        }
+    }
+}
+
 impl ByteDecoder for Plane {
     type Output = Plane;
 
@@ -263,6 +696,16 @@ impl ByteDecoder for Plane {
     }
 }
 
+impl ByteEncoder for Plane {
+    fn encode<W: Write>(&self, writer: &mut W) -> Result<()> {
+        write_vec3(writer, self.normal)?;
+        writer.write_f32::<LittleEndian>(self.dist)?;
+        writer.write_i32::<LittleEndian>(self.plane_type.into())?;
+
+        Ok(())
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 pub struct Edge {
     pub vertex: [u16; 2],
@@ -278,6 +721,12 @@ impl ByteDecoder for Edge {
     }
 }
 
+impl ByteEncoder for Edge {
+    fn encode<W: Write>(&self, writer: &mut W) -> Result<()> {
+        write_array_u16(writer, &self.vertex)
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 pub struct SurfEdge(pub i32);
 
@@ -289,6 +738,12 @@ impl ByteDecoder for SurfEdge {
     }
 }
 
+impl ByteEncoder for SurfEdge {
+    fn encode<W: Write>(&self, writer: &mut W) -> Result<()> {
+        Ok(writer.write_i32::<LittleEndian>(self.0)?)
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 pub struct Lighting {
     pub r: u8,
@@ -308,6 +763,12 @@ impl ByteDecoder for Lighting {
     }
 }
 
+impl ByteEncoder for Lighting {
+    fn encode<W: Write>(&self, writer: &mut W) -> Result<()> {
+        Ok(writer.write_all(&[self.r, self.g, self.b])?)
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 pub struct Vertex(pub glam::Vec3);
 
@@ -319,6 +780,12 @@ impl ByteDecoder for Vertex {
     }
 }
 
+impl ByteEncoder for Vertex {
+    fn encode<W: Write>(&self, writer: &mut W) -> Result<()> {
+        write_vec3(writer, self.0)
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 pub struct Node {
     pub idx_plane: u32,
@@ -344,6 +811,46 @@ impl ByteDecoder for Node {
     }
 }
 
+impl ByteEncoder for Node {
+    fn encode<W: Write>(&self, writer: &mut W) -> Result<()> {
+        writer.write_u32::<LittleEndian>(self.idx_plane)?;
+        write_array_i16(writer, &self.idx_children)?;
+        write_array_i16(writer, &self.mins)?;
+        write_array_i16(writer, &self.maxs)?;
+        writer.write_u16::<LittleEndian>(self.first_face)?;
+        writer.write_u16::<LittleEndian>(self.num_faces)?;
+
+        Ok(())
+    }
+}
+
+/// Node of the collision hulls, negative children are contents
+#[derive(Debug, Clone, Copy)]
+pub struct ClipNode {
+    pub idx_plane: i32,
+    pub idx_children: [i16; 2],
+}
+
+impl ByteDecoder for ClipNode {
+    type Output = ClipNode;
+
+    fn decode<R: Read + Seek>(reader: &mut R) -> Result<ClipNode> {
+        Ok(ClipNode {
+            idx_plane: reader.read_i32::<LittleEndian>()?,
+            idx_children: read_array_i16(reader)?,
+        })
+    }
+}
+
+impl ByteEncoder for ClipNode {
+    fn encode<W: Write>(&self, writer: &mut W) -> Result<()> {
+        writer.write_i32::<LittleEndian>(self.idx_plane)?;
+        write_array_i16(writer, &self.idx_children)?;
+
+        Ok(())
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 pub struct Leaf {
     pub contents: Contents,
@@ -399,6 +906,28 @@ impl TryFrom<i32> for Contents {
     }
 }
 
+impl From<Contents> for i32 {
+    fn from(value: Contents) -> i32 {
+        match value {
+            Contents::Empty => -1,
+            Contents::Solid => -2,
+            Contents::Water => -3,
+            Contents::Slime => -4,
+            Contents::Lava => -5,
+            Contents::Sky => -6,
+            Contents::Origin => -7,
+            Contents::Clip => -8,
+            Contents::Current0 => -9,
+            Contents::Current90 => -10,
+            Contents::Current180 => -11,
+            Contents::Current270 => -12,
+            Contents::CurrentUp => -13,
+            Contents::CurrentDown => -14,
+            Contents::Translucent => -15,
+        }
+    }
+}
+
 impl ByteDecoder for Leaf {
     type Output = Leaf;
 
@@ -415,6 +944,20 @@ impl ByteDecoder for Leaf {
     }
 }
 
+impl ByteEncoder for Leaf {
+    fn encode<W: Write>(&self, writer: &mut W) -> Result<()> {
+        writer.write_i32::<LittleEndian>(self.contents.into())?;
+        writer.write_i32::<LittleEndian>(self.vis_offset)?;
+        write_array_i16(writer, &self.mins)?;
+        write_array_i16(writer, &self.maxs)?;
+        writer.write_u16::<LittleEndian>(self.idx_first_mark_surface)?;
+        writer.write_u16::<LittleEndian>(self.num_mark_surfaces)?;
+        writer.write_all(&self.ambient_levels)?;
+
+        Ok(())
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 pub struct Visibility(pub u8);
 
@@ -426,6 +969,12 @@ impl ByteDecoder for Visibility {
     }
 }
 
+impl ByteEncoder for Visibility {
+    fn encode<W: Write>(&self, writer: &mut W) -> Result<()> {
+        Ok(writer.write_u8(self.0)?)
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 pub struct TextureInfo {
     pub s_vector: glam::Vec3,
@@ -451,6 +1000,19 @@ impl ByteDecoder for TextureInfo {
     }
 }
 
+impl ByteEncoder for TextureInfo {
+    fn encode<W: Write>(&self, writer: &mut W) -> Result<()> {
+        write_vec3(writer, self.s_vector)?;
+        writer.write_f32::<LittleEndian>(self.s_shift)?;
+        write_vec3(writer, self.t_vector)?;
+        writer.write_f32::<LittleEndian>(self.t_shift)?;
+        writer.write_u32::<LittleEndian>(self.idx_miptex)?;
+        writer.write_u32::<LittleEndian>(self.flags)?;
+
+        Ok(())
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 pub struct Face {
     pub plane: u16,
@@ -478,6 +1040,43 @@ impl ByteDecoder for Face {
     }
 }
 
+impl ByteEncoder for Face {
+    fn encode<W: Write>(&self, writer: &mut W) -> Result<()> {
+        writer.write_u16::<LittleEndian>(self.plane)?;
+        writer.write_u16::<LittleEndian>(self.plane_side)?;
+        writer.write_u32::<LittleEndian>(self.first_edge)?;
+        writer.write_u16::<LittleEndian>(self.edges)?;
+        writer.write_u16::<LittleEndian>(self.texture_info)?;
+        writer.write_all(&self.styles)?;
+        writer.write_u32::<LittleEndian>(self.lightmap_offset)?;
+
+        Ok(())
+    }
+}
+
+/// A face's vertices in order, with what's needed to draw it
+#[derive(Debug, Clone)]
+pub struct FacePolygon {
+    pub positions: Vec<glam::Vec3>,
+    pub normal: glam::Vec3,
+    /// Index into `textures`, if the face's texture info points at one
+    pub idx_miptex: Option<usize>,
+    /// Texture coordinates of each vertex in texels, divide by the texture
+    /// size to get UVs
+    pub texels: Vec<glam::Vec2>,
+    pub lightmap: Option<FaceLightmap>,
+}
+
+/// The first light style of a face, as RGB rows
+#[derive(Debug, Clone)]
+pub struct FaceLightmap {
+    pub width: u32,
+    pub height: u32,
+    pub data: Vec<u8>,
+    /// Coordinates of each vertex in luxels, from the lightmap's corner
+    pub luxels: Vec<glam::Vec2>,
+}
+
 #[derive(Debug, Clone)]
 pub struct Texture {
     pub name: [u8; MAXTEXTURENAME],
@@ -485,10 +1084,65 @@ pub struct Texture {
     pub height: u32,
     pub offsets: [u32; MIPLEVELS],
     pub mip: Vec<u8>,
+    /// Mip levels 1 to 3, each half the size of the one before. Empty for
+    /// textures that are only named here and stored in a WAD
+    pub lower_mips: [Vec<u8>; MIPLEVELS - 1],
     pub palette: [[u8; 3]; 256],
 }
 
 impl Texture {
+    /// Stands in for a texture lump entry with no texture, written back as
+    /// an offset of -1
+    pub fn placeholder() -> Texture {
+        Texture {
+            name: [0; MAXTEXTURENAME],
+            width: 0,
+            height: 0,
+            offsets: [0; MIPLEVELS],
+            mip: vec![],
+            lower_mips: Default::default(),
+            palette: [[0; 3]; 256],
+        }
+    }
+
+    pub fn is_placeholder(&self) -> bool {
+        self.width == 0 && self.height == 0 && self.name == [0; MAXTEXTURENAME]
+    }
+
+    /// Name up to the first null byte
+    pub fn name_string(&self) -> String {
+        let name = String::from_utf8_lossy(&self.name);
+
+        name.split('\0').next().unwrap_or_default().to_string()
+    }
+
+    /// A mip level as RGBA8 rows, `None` for textures stored in a WAD. `{`
+    /// textures are transparent where they use the last palette color
+    pub fn rgba(&self, level: usize) -> Option<Vec<u8>> {
+        let mip = match level {
+            0 => &self.mip,
+            _ => self.lower_mips.get(level - 1)?,
+        };
+
+        if self.offsets[0] == 0 || mip.is_empty() {
+            return None;
+        }
+
+        let transparent = self.name[0] == b'{';
+
+        let data = mip
+            .iter()
+            .flat_map(|&idx| {
+                let [r, g, b] = self.palette[idx as usize];
+                let a = if transparent && idx == 255 { 0 } else { 255 };
+
+                [r, g, b, a]
+            })
+            .collect();
+
+        Some(data)
+    }
+
     pub(crate) fn decode<R: Read + Seek>(reader: &mut R, offset: usize) -> Result<Texture> {
         let name = read_array_u8(reader)?;
         let width = reader.read_u32::<LittleEndian>()?;
@@ -512,14 +1166,27 @@ impl Texture {
             ];
         }
 
-        let mip_offset = offset + offsets[0] as usize;
-        let mip_len = (width * height) as usize;
+        let mut read_mip = |level: usize| {
+            let mip_offset = offset + offsets[level] as usize;
+            let mip_len = ((width >> level) * (height >> level)) as usize;
 
-        let mut mip = vec![0; mip_len];
+            let mut mip = vec![0; mip_len];
 
-        if reader.seek(SeekFrom::Start(mip_offset as u64)).is_ok() {
-            for i in mip.iter_mut() {
-                *i = reader.read_u8().unwrap_or_default();
+            if reader.seek(SeekFrom::Start(mip_offset as u64)).is_ok() {
+                for i in mip.iter_mut() {
+                    *i = reader.read_u8().unwrap_or_default();
+                }
+            }
+
+            mip
+        };
+
+        let mip = read_mip(0);
+        let mut lower_mips: [Vec<u8>; MIPLEVELS - 1] = Default::default();
+
+        if offsets[0] > 0 {
+            for (level, lower_mip) in lower_mips.iter_mut().enumerate() {
+                *lower_mip = read_mip(level + 1);
             }
         }
 
@@ -530,10 +1197,150 @@ impl Texture {
             offsets,
             palette,
             mip,
+            lower_mips,
         })
     }
 }
 
+impl ByteEncoder for Texture {
+    /// Writes the miptex, mips and palette only when the texture has them,
+    /// at the offsets [`mip_offsets`] gives
+    fn encode<W: Write>(&self, writer: &mut W) -> Result<()> {
+        writer.write_all(&self.name)?;
+        writer.write_u32::<LittleEndian>(self.width)?;
+        writer.write_u32::<LittleEndian>(self.height)?;
+
+        if self.offsets[0] == 0 {
+            return write_array_u32(writer, &self.offsets);
+        }
+
+        let mips = std::iter::once(&self.mip).chain(self.lower_mips.iter());
+
+        for (level, mip) in mips.clone().enumerate() {
+            if mip.len() != ((self.width >> level) * (self.height >> level)) as usize {
+                return Err(Error::Custom(format!(
+                    "Texture {} has the wrong size for mip level {}",
+                    self.name_string(),
+                    level
+                )));
+            }
+        }
+
+        write_array_u32(writer, &mip_offsets(self.width, self.height))?;
+
+        for mip in mips {
+            writer.write_all(mip)?;
+        }
+
+        writer.write_u16::<LittleEndian>(256)?;
+        for color in self.palette.iter() {
+            writer.write_all(color)?;
+        }
+
+        // Pads the texture to 4 bytes like the compile tools
+        writer.write_all(&[0; 2])?;
+
+        Ok(())
+    }
+}
+
+/// Where each mip level of a texture starts, from the start of its miptex
+pub(crate) fn mip_offsets(width: u32, height: u32) -> [u32; MIPLEVELS] {
+    let mut offsets = [0; MIPLEVELS];
+    let mut offset = (MAXTEXTURENAME + 8 + MIPLEVELS * 4) as u32;
+
+    for (level, mip_offset) in offsets.iter_mut().enumerate() {
+        *mip_offset = offset;
+        offset += (width >> level) * (height >> level);
+    }
+
+    offsets
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SequenceKind {
+    Animated,
+    Random,
+}
+
+/// Textures named `+0name` to `+9name` with alternates `+Aname` to `+Jname`, or
+/// random tiles `-0name` to `-9name`. Frames are indices into the texture list
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextureSequence {
+    pub name: String,
+    pub kind: SequenceKind,
+    pub frames: Vec<usize>,
+    pub alternate_frames: Vec<usize>,
+}
+
+type FrameSlots = [Option<usize>; 10];
+
+pub fn texture_sequences(textures: &[Texture]) -> Vec<TextureSequence> {
+    let mut sequences: Vec<TextureSequence> = vec![];
+    let mut slots: Vec<(FrameSlots, FrameSlots)> = vec![];
+
+    for (idx, texture) in textures.iter().enumerate() {
+        let name = texture.name_string().to_lowercase();
+        let mut chars = name.chars();
+
+        let kind = match chars.next() {
+            Some('+') => SequenceKind::Animated,
+            Some('-') => SequenceKind::Random,
+            _ => continue,
+        };
+
+        let (alternate, frame) = match (kind, chars.next()) {
+            (_, Some(c @ '0'..='9')) => (false, c as usize - '0' as usize),
+            (SequenceKind::Animated, Some(c @ 'a'..='j')) => (true, c as usize - 'a' as usize),
+            _ => continue,
+        };
+
+        let base = chars.as_str().to_string();
+
+        let position = sequences
+            .iter()
+            .position(|s| s.name == base && s.kind == kind)
+            .unwrap_or_else(|| {
+                sequences.push(TextureSequence {
+                    name: base,
+                    kind,
+                    frames: vec![],
+                    alternate_frames: vec![],
+                });
+                slots.push(([None; 10], [None; 10]));
+
+                sequences.len() - 1
+            });
+
+        let (frames, alternate_frames) = &mut slots[position];
+
+        if alternate {
+            alternate_frames[frame] = Some(idx);
+        } else {
+            frames[frame] = Some(idx);
+        }
+    }
+
+    // Like the engine, a sequence ends at the first missing frame
+    for (sequence, (frames, alternate_frames)) in sequences.iter_mut().zip(slots) {
+        let contiguous = |slots: FrameSlots| {
+            slots
+                .iter()
+                .take_while(|f| f.is_some())
+                .flatten()
+                .copied()
+                .collect()
+        };
+
+        sequence.frames = contiguous(frames);
+        sequence.alternate_frames = contiguous(alternate_frames);
+    }
+
+    sequences.retain(|s| !s.frames.is_empty());
+
+    sequences
+}
+
 #[derive(Debug, Clone, Copy)]
 pub struct MarkSurface(pub u16);
 
@@ -545,6 +1352,12 @@ impl ByteDecoder for MarkSurface {
     }
 }
 
+impl ByteEncoder for MarkSurface {
+    fn encode<W: Write>(&self, writer: &mut W) -> Result<()> {
+        Ok(writer.write_u16::<LittleEndian>(self.0)?)
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -555,4 +1368,501 @@ mod test {
 
         assert_eq!(size, 132);
     }
+
+    #[test]
+    fn test_face_polygon() {
+        let vertex = |x, y| Vertex(glam::Vec3::new(x, y, 0.0));
+
+        let bsp = GoldSrc30Bsp {
+            entities: vec![],
+            models: vec![],
+            planes: vec![Plane {
+                normal: glam::Vec3::Z,
+                dist: 0.0,
+                plane_type: PlaneType::Z,
+            }],
+            textures: vec![],
+            edges: vec![
+                Edge { vertex: [0, 0] },
+                Edge { vertex: [0, 1] },
+                Edge { vertex: [1, 2] },
+                Edge { vertex: [3, 2] },
+            ],
+            surf_edges: vec![SurfEdge(1), SurfEdge(2), SurfEdge(-3)],
+            lighting: (0..9).map(|i| Lighting { r: i, g: 0, b: 0 }).collect(),
+            vertices: vec![
+                vertex(0.0, 0.0),
+                vertex(32.0, 0.0),
+                vertex(32.0, 8.0),
+                vertex(0.0, 8.0),
+            ],
+            nodes: vec![],
+            clip_nodes: vec![],
+            leaves: vec![],
+            mark_surfaces: vec![],
+            visibility: vec![],
+            texture_info: vec![TextureInfo {
+                s_vector: glam::Vec3::X,
+                s_shift: 8.0,
+                t_vector: glam::Vec3::Y,
+                t_shift: 0.0,
+                idx_miptex: 0,
+                flags: 0,
+            }],
+            faces: vec![Face {
+                plane: 0,
+                plane_side: 1,
+                first_edge: 0,
+                edges: 3,
+                texture_info: 0,
+                styles: [0, 255, 255, 255],
+                lightmap_offset: 3,
+            }],
+            entity_errors: vec![],
+            original_lumps: vec![],
+        };
+
+        let polygon = bsp.face_polygon(0).unwrap();
+
+        assert_eq!(polygon.normal, -glam::Vec3::Z);
+        assert_eq!(polygon.positions[2], glam::Vec3::new(32.0, 8.0, 0.0));
+        assert_eq!(polygon.texels[1], glam::Vec2::new(40.0, 0.0));
+        assert_eq!(polygon.idx_miptex, None);
+
+        // Texels 8 to 40 and 0 to 8 span luxels 0 to 3 and 0 to 1
+        let lightmap = polygon.lightmap.unwrap();
+        assert_eq!((lightmap.width, lightmap.height), (4, 2));
+        assert_eq!(lightmap.data[..6], [1, 0, 0, 2, 0, 0]);
+        assert_eq!(lightmap.luxels[0], glam::Vec2::new(1.0, 0.5));
+
+        assert!(bsp.face_polygon(1).is_none());
+    }
+
+    #[test]
+    fn test_texture_sequences() {
+        let textures = [
+            "+0lab", "+2lab", "+1water", "+0water", "+AWATER", "+bwater", "+dwater", "-0rand",
+            "-1rand", "-arand", "crate", "+0rand",
+        ]
+        .iter()
+        .map(|name| {
+            let mut texture = Texture::placeholder();
+            texture.name[..name.len()].copy_from_slice(name.as_bytes());
+
+            texture
+        })
+        .collect::<Vec<_>>();
+
+        let sequence =
+            |name: &str, kind, frames: &[usize], alternate_frames: &[usize]| TextureSequence {
+                name: name.to_string(),
+                kind,
+                frames: frames.to_vec(),
+                alternate_frames: alternate_frames.to_vec(),
+            };
+
+        assert_eq!(
+            texture_sequences(&textures),
+            vec![
+                // Stops at the missing +1lab
+                sequence("lab", SequenceKind::Animated, &[0], &[]),
+                // Frame order, not texture order, and alternates stop at +c
+                sequence("water", SequenceKind::Animated, &[3, 2], &[4, 5]),
+                // Random tiles have no alternates
+                sequence("rand", SequenceKind::Random, &[7, 8], &[]),
+                sequence("rand", SequenceKind::Animated, &[11], &[]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_encode_round_trip() {
+        let texture = |name: &[u8], embedded: bool| {
+            let mut padded = [0; MAXTEXTURENAME];
+            padded[..name.len()].copy_from_slice(name);
+
+            let (offsets, mip, lower_mips) = if embedded {
+                let mip = (0..=255).collect::<Vec<u8>>();
+                let lower_mips = [vec![1; 64], vec![2; 16], vec![3; 4]];
+
+                (mip_offsets(16, 16), mip, lower_mips)
+            } else {
+                ([0; MIPLEVELS], vec![], Default::default())
+            };
+
+            let mut palette = [[0; 3]; 256];
+            palette[1] = [255, 128, 0];
+
+            Texture {
+                name: padded,
+                width: 16,
+                height: 16,
+                offsets,
+                mip,
+                lower_mips,
+                palette,
+            }
+        };
+
+        let bsp = GoldSrc30Bsp {
+            entities: entity::parse("{\n\"classname\" \"worldspawn\"\n}\n").unwrap(),
+            models: vec![Model {
+                mins: [-64.0, -64.0, 0.0],
+                maxs: [64.0, 64.0, 128.0],
+                origin: glam::Vec3::ZERO,
+                idx_head_nodes: [0, 0, 1, -1],
+                num_vis_leafs: 1,
+                idx_first_face: 0,
+                num_faces: 1,
+            }],
+            planes: vec![Plane {
+                normal: glam::Vec3::Z,
+                dist: 0.5,
+                plane_type: PlaneType::Z,
+            }],
+            textures: vec![texture(b"+0lab", true), texture(b"{grate", false)],
+            edges: vec![Edge { vertex: [0, 1] }],
+            surf_edges: vec![SurfEdge(-1)],
+            lighting: vec![Lighting { r: 1, g: 2, b: 3 }],
+            vertices: vec![Vertex(glam::Vec3::new(1.0, 2.0, 3.0))],
+            nodes: vec![Node {
+                idx_plane: 0,
+                idx_children: [-1, -2],
+                mins: [-64, -64, 0],
+                maxs: [64, 64, 128],
+                first_face: 0,
+                num_faces: 1,
+            }],
+            clip_nodes: vec![ClipNode {
+                idx_plane: 0,
+                idx_children: [-1, -2],
+            }],
+            leaves: vec![Leaf {
+                contents: Contents::Water,
+                vis_offset: -1,
+                mins: [-64, -64, 0],
+                maxs: [64, 64, 128],
+                idx_first_mark_surface: 0,
+                num_mark_surfaces: 1,
+                ambient_levels: [0, 1, 2, 3],
+            }],
+            mark_surfaces: vec![MarkSurface(0)],
+            visibility: vec![Visibility(0xff)],
+            texture_info: vec![TextureInfo {
+                s_vector: glam::Vec3::X,
+                s_shift: 0.25,
+                t_vector: glam::Vec3::Y,
+                t_shift: -8.0,
+                idx_miptex: 1,
+                flags: 0,
+            }],
+            faces: vec![Face {
+                plane: 0,
+                plane_side: 1,
+                first_edge: 0,
+                edges: 1,
+                texture_info: 0,
+                styles: [0, 255, 255, 255],
+                lightmap_offset: 0,
+            }],
+            entity_errors: vec![],
+            original_lumps: vec![],
+        };
+
+        let mut writer = std::io::Cursor::new(vec![]);
+        encode(&mut writer, &bsp).unwrap();
+        let data = writer.into_inner();
+
+        let mut reader = std::io::Cursor::new(data.clone());
+        reader.set_position(4);
+        let header = decode_header(&mut reader, 30).unwrap();
+
+        for lump_type in LUMP_ORDER.iter() {
+            assert_eq!(header.lumps[*lump_type as usize].file_offset % 4, 0);
+        }
+
+        let entities = header.lumps[LumpType::Entities as usize];
+        let start = entities.file_offset as usize;
+        assert_eq!(
+            &data[start..start + entities.len as usize],
+            b"{\n\"classname\" \"worldspawn\"\n}\n\0"
+        );
+
+        reader.set_position(4);
+        let mut decoded = decode(&mut reader, 30).unwrap();
+
+        assert_eq!(decoded.clip_nodes[0].idx_children, [-1, -2]);
+        assert!(matches!(decoded.leaves[0].contents, Contents::Water));
+        assert_eq!(decoded.textures.len(), 2);
+        assert_eq!(decoded.textures[0].lower_mips[2], vec![3; 4]);
+        assert_eq!(decoded.textures[0].palette[1], [255, 128, 0]);
+        assert_eq!(decoded.textures[1].name_string(), "{grate");
+        assert_eq!(decoded.textures[1].offsets, [0; MIPLEVELS]);
+
+        let mut writer = std::io::Cursor::new(vec![]);
+        encode(&mut writer, &decoded).unwrap();
+        assert_eq!(writer.into_inner(), data);
+
+        let mut rgba = vec![];
+        for idx in 0..16 * 16 {
+            rgba.extend_from_slice(&[255, 0, 0, if idx == 0 { 0 } else { 255 }]);
+        }
+
+        let mut wad = crate::wad::Encoder::new(std::io::Cursor::new(vec![])).unwrap();
+        wad.add_texture("{GRATE", 16, 16, &rgba).unwrap();
+        let mut wad = wad.finish().unwrap();
+        wad.set_position(0);
+
+        decoded.textures.push(Texture {
+            name: *b"missing\0\0\0\0\0\0\0\0\0",
+            ..decoded.textures[1].clone()
+        });
+
+        let mut wads = [WadIndex::new(wad).unwrap()];
+        assert_eq!(decoded.embed_textures(&mut wads), vec!["missing"]);
+
+        let grate = &decoded.textures[1];
+        assert_eq!(grate.name_string(), "{grate");
+        assert_eq!(grate.offsets, mip_offsets(16, 16));
+        assert_eq!(grate.mip[0], 255);
+        assert_eq!(grate.palette[grate.mip[1] as usize], [255, 0, 0]);
+        assert_eq!(grate.lower_mips[2].len(), 4);
+        assert_eq!(
+            &grate.rgba(0).unwrap()[..8],
+            &[0, 0, 255, 0, 255, 0, 0, 255]
+        );
+        assert_eq!(grate.rgba(3).unwrap().len(), 16);
+        assert!(decoded.textures[2].rgba(0).is_none());
+
+        let mut writer = std::io::Cursor::new(vec![]);
+        encode(&mut writer, &decoded).unwrap();
+
+        let mut reader = std::io::Cursor::new(writer.into_inner());
+        reader.set_position(4);
+        let embedded = decode(&mut reader, 30).unwrap();
+        assert_eq!(embedded.textures[1].mip, grate.mip);
+    }
+
+    #[test]
+    fn test_decode_encode_lumps() {
+        let miptex = |name: &[u8], size: u32, embedded: bool| {
+            let mut data = name.to_vec();
+            data.resize(MAXTEXTURENAME, 0);
+            data.extend_from_slice(&size.to_le_bytes());
+            data.extend_from_slice(&size.to_le_bytes());
+
+            let offsets = if embedded {
+                mip_offsets(size, size)
+            } else {
+                [0; MIPLEVELS]
+            };
+            for offset in offsets.iter() {
+                data.extend_from_slice(&offset.to_le_bytes());
+            }
+
+            if embedded {
+                for level in 0..MIPLEVELS {
+                    data.extend((0..(size * size) >> (2 * level)).map(|i| i as u8));
+                }
+
+                data.extend_from_slice(&256u16.to_le_bytes());
+                data.extend((0..768).map(|i| (i / 3) as u8));
+                data.extend_from_slice(&[0; 2]);
+            }
+
+            data
+        };
+
+        // A directory like the compile tools write, the unused entry in the
+        // middle has an offset of -1. The last entry is cut off by the end of
+        // the file, so it can't be read
+        let sky = miptex(b"sky", 16, false);
+        let grate = miptex(b"{grate", 16, true);
+        let dir_len = 4 + 4 * 4;
+        let mut textures = 4u32.to_le_bytes().to_vec();
+        for offset in [
+            dir_len,
+            u32::MAX,
+            dir_len + sky.len() as u32,
+            dir_len + (sky.len() + grate.len()) as u32,
+        ]
+        .iter()
+        {
+            textures.extend_from_slice(&offset.to_le_bytes());
+        }
+        textures.extend_from_slice(&sky);
+        textures.extend_from_slice(&grate);
+        textures.extend_from_slice(b"cut off\0");
+
+        let mut planes = vec![];
+        for value in [0.0f32, 0.0, 1.0, 64.0].iter() {
+            planes.extend_from_slice(&value.to_le_bytes());
+        }
+        planes.extend_from_slice(&2i32.to_le_bytes());
+
+        let mut texinfo = vec![];
+        for value in [1.0f32, 0.0, 0.0, 8.0, 0.0, -1.0, 0.0, 0.0].iter() {
+            texinfo.extend_from_slice(&value.to_le_bytes());
+        }
+        texinfo.extend_from_slice(&2u32.to_le_bytes());
+        texinfo.extend_from_slice(&0u32.to_le_bytes());
+
+        // The light is missing a value, it's skipped but written back as it was
+        let entities = b"{\n\"classname\" \"worldspawn\"\n\"wad\" \"halflife.wad\"\n}\n\
+            {\n\"classname\" \"light\"\n\"_light\" }\n\0"
+            .to_vec();
+
+        // Stored in another order than the encoder's, lumps are compared by
+        // their contents
+        let lumps = [
+            (LumpType::Texinfo, texinfo),
+            (LumpType::Entities, entities),
+            (LumpType::Planes, planes),
+            // Two colors and part of a third
+            (LumpType::Lighting, vec![1, 2, 3, 4, 5, 6, 7]),
+            (LumpType::Textures, textures),
+        ];
+
+        let file = lump_file(&lumps);
+        let original = file_lumps(&file);
+
+        let mut reader = std::io::Cursor::new(file.clone());
+        reader.set_position(4);
+        let bsp = decode(&mut reader, 30).unwrap();
+
+        assert_eq!(bsp.textures.len(), 4);
+        assert!(bsp.textures[1].is_placeholder());
+        assert_eq!(bsp.textures[2].name_string(), "{grate");
+        assert!(bsp.textures[3].is_placeholder());
+        assert_eq!(bsp.texture_info[0].idx_miptex, 2);
+        assert_eq!(bsp.lighting.len(), 2);
+        assert_eq!(bsp.entities.len(), 1);
+        assert_eq!(bsp.entity_errors.len(), 1);
+
+        let encoded_lumps = |bsp: &GoldSrc30Bsp| {
+            let mut writer = std::io::Cursor::new(vec![]);
+            encode(&mut writer, bsp).unwrap();
+
+            file_lumps(&writer.into_inner())
+        };
+
+        let encoded = encoded_lumps(&bsp);
+
+        for lump_type in LUMP_ORDER.iter() {
+            assert_eq!(
+                encoded[*lump_type as usize], original[*lump_type as usize],
+                "{:?} lump differs",
+                lump_type
+            );
+        }
+
+        // Once changed, lumps are written from what was decoded
+        let mut bsp = bsp;
+        bsp.lighting.push(Lighting { r: 8, g: 9, b: 10 });
+
+        let encoded = encoded_lumps(&bsp);
+        assert_eq!(
+            encoded[LumpType::Lighting as usize],
+            vec![1, 2, 3, 4, 5, 6, 8, 9, 10]
+        );
+        assert_eq!(
+            encoded[LumpType::Textures as usize],
+            original[LumpType::Textures as usize]
+        );
+    }
+
+    #[test]
+    fn test_replace_entities() {
+        let lumps = [
+            (LumpType::Lighting, vec![1, 2, 3, 4, 5, 6, 7]),
+            (
+                LumpType::Entities,
+                b"{\n\"classname\" \"worldspawn\"\n}\n\0".to_vec(),
+            ),
+            (LumpType::Planes, vec![9; 20]),
+            (LumpType::Models, vec![5; 64]),
+        ];
+        let file = lump_file(&lumps);
+
+        let entities =
+            entity::parse("{\n\"classname\" \"worldspawn\"\n\"skyname\" \"desert\"\n}\n").unwrap();
+
+        let mut writer = std::io::Cursor::new(vec![]);
+        replace_entities(
+            &mut std::io::Cursor::new(file.clone()),
+            &mut writer,
+            &entities,
+        )
+        .unwrap();
+        let replaced = writer.into_inner();
+
+        let (original, replaced_lumps) = (file_lumps(&file), file_lumps(&replaced));
+
+        for lump_type in LUMP_ORDER.iter() {
+            let expected = if *lump_type == LumpType::Entities {
+                encode_entities(&entities)
+            } else {
+                original[*lump_type as usize].clone()
+            };
+
+            assert_eq!(
+                replaced_lumps[*lump_type as usize], expected,
+                "{:?} lump differs",
+                lump_type
+            );
+        }
+
+        // Lumps stay in the order they were stored, each on a 4 byte boundary
+        let mut reader = std::io::Cursor::new(replaced);
+        reader.set_position(4);
+        let header = decode_header(&mut reader, 30).unwrap();
+        let offsets = lumps
+            .iter()
+            .map(|(lump_type, _)| header.lumps[*lump_type as usize].file_offset)
+            .collect::<Vec<_>>();
+
+        assert!(offsets.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(offsets.iter().all(|offset| offset & 3 == 0));
+    }
+
+    /// A file with the lumps stored in the order given, lumps it doesn't
+    /// list are empty
+    fn lump_file(lumps: &[(LumpType, Vec<u8>)]) -> Vec<u8> {
+        let header_len = 4 + LumpType::HeaderLumps as usize * 8;
+        let mut locations = [(header_len, 0); NUM_LUMPS];
+        let mut body = vec![];
+
+        for (lump_type, data) in lumps.iter() {
+            locations[*lump_type as usize] = (header_len + body.len(), data.len());
+            body.extend_from_slice(data);
+            body.resize(body.len() + (4 - body.len() % 4) % 4, 0);
+        }
+
+        let mut file = 30i32.to_le_bytes().to_vec();
+        for (offset, len) in locations.iter().take(LumpType::HeaderLumps as usize) {
+            file.extend_from_slice(&(*offset as i32).to_le_bytes());
+            file.extend_from_slice(&(*len as i32).to_le_bytes());
+        }
+        file.extend_from_slice(&body);
+
+        file
+    }
+
+    /// Bytes of each lump in a file, indexed by lump type
+    fn file_lumps(file: &[u8]) -> Vec<Vec<u8>> {
+        let mut reader = std::io::Cursor::new(file);
+        reader.set_position(4);
+        let header = decode_header(&mut reader, 30).unwrap();
+
+        header
+            .lumps
+            .iter()
+            .take(LumpType::HeaderLumps as usize)
+            .map(|lump| {
+                let start = lump.file_offset as usize;
+
+                file[start..start + lump.len as usize].to_vec()
+            })
+            .collect()
+    }
 }
@@ -1,10 +1,16 @@
-use std::io::{Read, Seek};
+use std::io::{Read, Seek, Write};
 
 use byteorder::{LittleEndian, ReadBytesExt};
 
 pub(crate) mod common;
+pub mod entity;
 mod error;
 pub mod format;
+pub mod map;
+pub mod pak;
+mod quantize;
+pub mod tga;
+pub mod vfs;
 pub mod wad;
 
 pub use error::Error;
@@ -54,6 +60,36 @@ impl<R: Read + Seek> BspDecoder<R> {
     }
 }
 
+/// Writes a decoded map back to a `.bsp`
+#[derive(Debug)]
+pub struct BspEncoder<W: Write + Seek> {
+    writer: W,
+}
+
+impl<W: Write + Seek> BspEncoder<W> {
+    pub fn from_writer(writer: W) -> Self {
+        BspEncoder { writer }
+    }
+
+    pub fn encode_gold_src_30(mut self, bsp: &GoldSrc30Bsp) -> Result<W> {
+        gold_src_30::encode(&mut self.writer, bsp)?;
+
+        Ok(self.writer)
+    }
+
+    /// Copies the map `reader` is at the start of with its entity lump
+    /// replaced, its other lumps aren't decoded and keep their bytes
+    pub fn replace_entities_gold_src_30<R: Read + Seek>(
+        mut self,
+        reader: &mut R,
+        entities: &[entity::Entity],
+    ) -> Result<W> {
+        gold_src_30::replace_entities(reader, &mut self.writer, entities)?;
+
+        Ok(self.writer)
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum BspVersion {
     GoldSrc30,
@@ -86,6 +122,88 @@ impl<R: Read + Seek> WadDecoder<R> {
     pub fn decode(mut self) -> Result<wad::Wad> {
         wad::decode(&mut self.reader)
     }
+
+    /// Reads only the directory, for looking up and decoding single lumps
+    pub fn index(self) -> Result<wad::WadIndex<R>> {
+        wad::WadIndex::new(self.reader)
+    }
+}
+
+#[derive(Debug)]
+pub struct PakDecoder<R: Read + Seek> {
+    reader: R,
+}
+
+impl<R: Read + Seek> PakDecoder<R> {
+    pub fn from_reader(reader: R) -> Self {
+        PakDecoder { reader }
+    }
+
+    pub fn decode(mut self) -> Result<pak::Pak> {
+        pak::decode(&mut self.reader)
+    }
+}
+
+/// Writes a pak, file by file, then its directory on `finish`
+#[derive(Debug)]
+pub struct PakEncoder<W: Write + Seek> {
+    encoder: pak::Encoder<W>,
+}
+
+impl<W: Write + Seek> PakEncoder<W> {
+    pub fn from_writer(writer: W) -> Result<Self> {
+        Ok(PakEncoder {
+            encoder: pak::Encoder::new(writer)?,
+        })
+    }
+
+    /// Adds a file at `name`, a path inside the pak like `maps/c1a0.bsp`
+    pub fn add(&mut self, name: &str, data: &[u8]) -> Result<()> {
+        self.encoder.add(name, data)
+    }
+
+    pub fn finish(self) -> Result<W> {
+        self.encoder.finish()
+    }
+}
+
+/// Writes a Half-Life WAD3 of mip textures, then its directory on `finish`
+#[derive(Debug)]
+pub struct WadEncoder<W: Write + Seek> {
+    encoder: wad::Encoder<W>,
+}
+
+impl<W: Write + Seek> WadEncoder<W> {
+    pub fn from_writer(writer: W) -> Result<Self> {
+        Ok(WadEncoder {
+            encoder: wad::Encoder::new(writer)?,
+        })
+    }
+
+    /// Adds a texture from RGBA8 rows, quantized to its own 256 color palette
+    /// with four mip levels. Sizes must be multiples of 16
+    pub fn add_texture(&mut self, name: &str, width: u32, height: u32, rgba: &[u8]) -> Result<()> {
+        self.encoder.add_texture(name, width, height, rgba)
+    }
+
+    pub fn finish(self) -> Result<W> {
+        self.encoder.finish()
+    }
+}
+
+#[derive(Debug)]
+pub struct TgaDecoder<R: Read + Seek> {
+    reader: R,
+}
+
+impl<R: Read + Seek> TgaDecoder<R> {
+    pub fn from_reader(reader: R) -> Self {
+        TgaDecoder { reader }
+    }
+
+    pub fn decode(mut self) -> Result<tga::Image> {
+        tga::decode(&mut self.reader)
+    }
 }
 
 trait ByteDecoder {
@@ -93,3 +211,7 @@ trait ByteDecoder {
 
     fn decode<R: Read + Seek>(reader: &mut R) -> Result<Self::Output>;
 }
+
+trait ByteEncoder {
+    fn encode<W: Write>(&self, writer: &mut W) -> Result<()>;
+}
@@ -0,0 +1,497 @@
+use std::cmp::Ordering;
+
+use glam::DVec3;
+
+use super::winding::brush_windings;
+use super::{default_texture_axes, Brush, BrushFace, Map, MapEntity};
+use crate::entity::Entity;
+use crate::format::gold_src_30::{Contents, GoldSrc30Bsp, TextureInfo};
+
+/// Texture of brush faces no map face lies on, the compile tools drop them
+const HIDDEN_TEXTURE: &str = "NULL";
+
+const ORIGIN_TEXTURE: &str = "ORIGIN";
+
+/// Rebuilds the source of a compiled map. Every leaf of a model's hull 0
+/// tree that isn't empty becomes a brush bounded by the node planes above
+/// it, textured like the map faces lying on its faces. Brushes come back
+/// the way the tree cut them up, not as they were drawn
+pub fn decompile(bsp: &GoldSrc30Bsp) -> Map {
+    let faces = (0..bsp.faces.len())
+        .map(|idx| MapFace::new(bsp, idx))
+        .collect::<Vec<_>>();
+
+    let mut entities = bsp.entities.clone();
+
+    if !entities
+        .iter()
+        .any(|entity| entity.classname() == Some("worldspawn"))
+    {
+        entities.insert(
+            0,
+            Entity {
+                properties: vec![("classname".to_string(), "worldspawn".to_string())],
+            },
+        );
+    }
+
+    let entities = entities
+        .into_iter()
+        .map(|mut entity| {
+            let model = entity.brush_model().filter(|idx| *idx < bsp.models.len());
+
+            // The compile tools number brush models themselves
+            entity
+                .properties
+                .retain(|(key, value)| !(key == "model" && value.starts_with('*')));
+
+            if entity.classname() == Some("worldspawn") {
+                entity.properties.retain(|(key, _)| key != "mapversion");
+                entity
+                    .properties
+                    .insert(1.min(entity.properties.len()), mapversion());
+            }
+
+            let mut brushes = model
+                .map(|idx| model_brushes(bsp, idx, &faces))
+                .unwrap_or_default();
+
+            // Models of entities with an origin brush are stored around it,
+            // their texture shifts include the origin along the texture axes
+            if let (Some(_), Some(origin)) = (model.filter(|idx| *idx > 0), entity.origin()) {
+                let origin = origin.as_f64();
+
+                for face in brushes.iter_mut().flat_map(|brush| brush.faces.iter_mut()) {
+                    for point in face.points.iter_mut() {
+                        *point += origin;
+                    }
+
+                    face.u_shift -= origin.dot(face.u_axis / face.scale[0]);
+                    face.v_shift -= origin.dot(face.v_axis / face.scale[1]);
+                }
+
+                let half = DVec3::splat(8.0);
+                brushes.push(box_brush(origin - half, origin + half, ORIGIN_TEXTURE));
+            }
+
+            MapEntity { entity, brushes }
+        })
+        .collect();
+
+    Map { entities }
+}
+
+fn mapversion() -> (String, String) {
+    ("mapversion".to_string(), "220".to_string())
+}
+
+/// A map face as the decompiler looks textures up, in double precision
+struct MapFace {
+    normal: DVec3,
+    dist: f64,
+    positions: Vec<DVec3>,
+    centroid: DVec3,
+    texture_info: usize,
+}
+
+impl MapFace {
+    fn new(bsp: &GoldSrc30Bsp, idx: usize) -> Option<MapFace> {
+        let polygon = bsp.face_polygon(idx)?;
+
+        if polygon.positions.len() < 3 {
+            return None;
+        }
+
+        let normal = polygon.normal.as_f64();
+        let positions = polygon
+            .positions
+            .iter()
+            .map(|position| position.as_f64())
+            .collect::<Vec<_>>();
+
+        Some(MapFace {
+            normal,
+            dist: normal.dot(positions[0]),
+            centroid: centroid(&positions),
+            positions,
+            texture_info: bsp.faces[idx].texture_info as usize,
+        })
+    }
+
+    /// Whether a point on the face's plane is inside it, faces wind clockwise
+    fn contains(&self, point: DVec3) -> bool {
+        (0..self.positions.len()).all(|idx| {
+            let start = self.positions[idx];
+            let end = self.positions[(idx + 1) % self.positions.len()];
+
+            (end - start).cross(point - start).dot(self.normal) <= 0.01
+        })
+    }
+}
+
+fn model_brushes(bsp: &GoldSrc30Bsp, idx: usize, faces: &[Option<MapFace>]) -> Vec<Brush> {
+    let model = &bsp.models[idx];
+
+    let first_face = model.idx_first_face.max(0) as usize;
+    let last_face = (first_face + model.num_faces.max(0) as usize).min(faces.len());
+    let faces = faces[first_face.min(last_face)..last_face]
+        .iter()
+        .flatten()
+        .collect::<Vec<_>>();
+
+    // Leaves reaching past the model, like the void around the world, are
+    // cut off just outside its bounds
+    let mins = glam::Vec3::from(model.mins).as_f64() - DVec3::ONE;
+    let maxs = glam::Vec3::from(model.maxs).as_f64() + DVec3::ONE;
+    let mut planes = box_planes(mins, maxs);
+
+    let mut brushes = vec![];
+    walk(
+        bsp,
+        model.idx_head_nodes[0],
+        &mut planes,
+        &faces,
+        &mut brushes,
+    );
+
+    brushes
+}
+
+/// Collects a brush for every leaf below `child`, a node index or the
+/// complement of a leaf index. `planes` bound the space `child` covers
+fn walk(
+    bsp: &GoldSrc30Bsp,
+    child: i32,
+    planes: &mut Vec<(DVec3, f64)>,
+    faces: &[&MapFace],
+    brushes: &mut Vec<Brush>,
+) {
+    if child < 0 {
+        let leaf = match bsp.leaves.get(!child as usize) {
+            Some(leaf) => leaf,
+            None => return,
+        };
+
+        if !matches!(leaf.contents, Contents::Empty) {
+            brushes.extend(brush(bsp, planes, faces));
+        }
+
+        return;
+    }
+
+    let node = match bsp.nodes.get(child as usize) {
+        Some(node) => node,
+        None => return,
+    };
+    let plane = match bsp.planes.get(node.idx_plane as usize) {
+        Some(plane) => plane,
+        None => return,
+    };
+
+    let normal = plane.normal.as_f64();
+    let dist = plane.dist as f64;
+
+    // The front child is in front of the node plane, the space behind a
+    // flipped plane
+    planes.push((-normal, -dist));
+    walk(bsp, node.idx_children[0] as i32, planes, faces, brushes);
+    planes.pop();
+
+    planes.push((normal, dist));
+    walk(bsp, node.idx_children[1] as i32, planes, faces, brushes);
+    planes.pop();
+}
+
+fn brush(bsp: &GoldSrc30Bsp, planes: &[(DVec3, f64)], faces: &[&MapFace]) -> Option<Brush> {
+    let windings = brush_windings(planes);
+
+    let faces = planes
+        .iter()
+        .zip(windings.iter())
+        .filter(|(_, winding)| !winding.is_empty())
+        .map(|(&(normal, dist), winding)| {
+            let points = plane_points(winding);
+            let center = centroid(winding);
+
+            // The map face on the same plane nearest this face, if any
+            let face = faces
+                .iter()
+                .filter(|face| face.normal.dot(normal) > 0.999 && (face.dist - dist).abs() < 0.1)
+                .map(|face| {
+                    let distance = if face.contains(center) {
+                        0.0
+                    } else {
+                        face.centroid.distance(center)
+                    };
+
+                    (face, distance)
+                })
+                .min_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap_or(Ordering::Equal))
+                .map(|(face, _)| face);
+
+            let texture_info = face.and_then(|face| bsp.texture_info.get(face.texture_info));
+            let texture = texture_info
+                .and_then(|info| bsp.textures.get(info.idx_miptex as usize))
+                .map(|texture| texture.name_string());
+
+            match (texture_info, texture) {
+                (Some(info), Some(texture)) => textured_face(points, texture, info)
+                    .unwrap_or_else(|| untextured_face(points, normal, HIDDEN_TEXTURE)),
+                _ => untextured_face(points, normal, HIDDEN_TEXTURE),
+            }
+        })
+        .collect::<Vec<_>>();
+
+    if faces.len() < 4 {
+        None
+    } else {
+        Some(Brush { faces })
+    }
+}
+
+/// Texture vectors are axes divided by the scale, shifts are stored as is
+fn textured_face(points: [DVec3; 3], texture: String, info: &TextureInfo) -> Option<BrushFace> {
+    let s = info.s_vector.as_f64();
+    let t = info.t_vector.as_f64();
+
+    if s.length() < 1e-6 || t.length() < 1e-6 {
+        return None;
+    }
+
+    Some(BrushFace {
+        points,
+        texture,
+        u_axis: s.normalize(),
+        u_shift: info.s_shift as f64,
+        v_axis: t.normalize(),
+        v_shift: info.t_shift as f64,
+        rotation: 0.0,
+        scale: [1.0 / s.length(), 1.0 / t.length()],
+    })
+}
+
+fn untextured_face(points: [DVec3; 3], normal: DVec3, texture: &str) -> BrushFace {
+    let (u_axis, v_axis) = default_texture_axes(normal);
+
+    BrushFace {
+        points,
+        texture: texture.to_string(),
+        u_axis,
+        u_shift: 0.0,
+        v_axis,
+        v_shift: 0.0,
+        rotation: 0.0,
+        scale: [1.0, 1.0],
+    }
+}
+
+fn box_planes(mins: DVec3, maxs: DVec3) -> Vec<(DVec3, f64)> {
+    vec![
+        (DVec3::X, maxs.x),
+        (-DVec3::X, -mins.x),
+        (DVec3::Y, maxs.y),
+        (-DVec3::Y, -mins.y),
+        (DVec3::Z, maxs.z),
+        (-DVec3::Z, -mins.z),
+    ]
+}
+
+fn box_brush(mins: DVec3, maxs: DVec3, texture: &str) -> Brush {
+    let planes = box_planes(mins, maxs);
+
+    let faces = planes
+        .iter()
+        .zip(brush_windings(&planes))
+        .map(|(&(normal, _), winding)| untextured_face(plane_points(&winding), normal, texture))
+        .collect();
+
+    Brush { faces }
+}
+
+/// Three points of a winding, as far apart as they get so the plane they
+/// give survives rounding. Keeps the winding's clockwise order
+fn plane_points(winding: &[DVec3]) -> [DVec3; 3] {
+    let mut best = (1, 2, 0.0);
+
+    for i in 1..winding.len() - 1 {
+        for j in i + 1..winding.len() {
+            let area = (winding[i] - winding[0])
+                .cross(winding[j] - winding[0])
+                .length();
+
+            if area > best.2 {
+                best = (i, j, area);
+            }
+        }
+    }
+
+    [winding[0], winding[best.0], winding[best.1]]
+}
+
+fn centroid(points: &[DVec3]) -> DVec3 {
+    points.iter().fold(DVec3::ZERO, |sum, point| sum + *point) / points.len() as f64
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::entity;
+    use crate::format::gold_src_30::*;
+    use crate::map::serialize;
+
+    #[test]
+    fn test_decompile_box() {
+        let planes = [
+            (glam::Vec3::X, 16.0),
+            (-glam::Vec3::X, 16.0),
+            (glam::Vec3::Y, 16.0),
+            (-glam::Vec3::Y, 16.0),
+            (glam::Vec3::Z, 64.0),
+            (-glam::Vec3::Z, 0.0),
+        ];
+
+        // Empty in front of each plane and solid behind all of them
+        let nodes = (0..planes.len())
+            .map(|idx| Node {
+                idx_plane: idx as u32,
+                idx_children: [-2, if idx < 5 { idx as i16 + 1 } else { -1 }],
+                mins: [-16, -16, 0],
+                maxs: [16, 16, 64],
+                first_face: 0,
+                num_faces: 0,
+            })
+            .collect();
+
+        let leaf = |contents| Leaf {
+            contents,
+            vis_offset: -1,
+            mins: [0; 3],
+            maxs: [0; 3],
+            idx_first_mark_surface: 0,
+            num_mark_surfaces: 0,
+            ambient_levels: [0; 4],
+        };
+
+        let model = Model {
+            mins: [-16.0, -16.0, 0.0],
+            maxs: [16.0, 16.0, 64.0],
+            origin: glam::Vec3::ZERO,
+            idx_head_nodes: [0, -1, -1, -1],
+            num_vis_leafs: 1,
+            idx_first_face: 0,
+            num_faces: 1,
+        };
+
+        let mut name = [0; MAXTEXTURENAME];
+        name[..5].copy_from_slice(b"CRATE");
+
+        let bsp = GoldSrc30Bsp {
+            entities: entity::parse(
+                "{\n\"classname\" \"worldspawn\"\n\"mapversion\" \"220\"\n}\n\
+                {\n\"classname\" \"func_door_rotating\"\n\"model\" \"*1\"\n\"origin\" \"32 0 100\"\n}\n",
+            )
+            .unwrap(),
+            models: vec![model, model],
+            planes: planes
+                .iter()
+                .map(|&(normal, dist)| Plane {
+                    normal,
+                    dist,
+                    plane_type: PlaneType::AnyZ,
+                })
+                .collect(),
+            textures: vec![Texture {
+                name,
+                width: 64,
+                height: 64,
+                offsets: [0; MIPLEVELS],
+                mip: vec![],
+                lower_mips: Default::default(),
+                palette: [[0; 3]; 256],
+            }],
+            edges: vec![
+                Edge { vertex: [0, 1] },
+                Edge { vertex: [1, 2] },
+                Edge { vertex: [2, 3] },
+                Edge { vertex: [3, 0] },
+            ],
+            surf_edges: (0..4).map(SurfEdge).collect(),
+            lighting: vec![],
+            // The top of the box, clockwise seen from above
+            vertices: vec![
+                Vertex(glam::Vec3::new(16.0, 16.0, 64.0)),
+                Vertex(glam::Vec3::new(16.0, -16.0, 64.0)),
+                Vertex(glam::Vec3::new(-16.0, -16.0, 64.0)),
+                Vertex(glam::Vec3::new(-16.0, 16.0, 64.0)),
+            ],
+            nodes,
+            clip_nodes: vec![],
+            leaves: vec![leaf(Contents::Solid), leaf(Contents::Empty)],
+            mark_surfaces: vec![],
+            visibility: vec![],
+            texture_info: vec![TextureInfo {
+                s_vector: glam::Vec3::X * 0.5,
+                s_shift: 4.0,
+                t_vector: -glam::Vec3::Y,
+                t_shift: 0.0,
+                idx_miptex: 0,
+                flags: 0,
+            }],
+            faces: vec![Face {
+                plane: 4,
+                plane_side: 0,
+                first_edge: 0,
+                edges: 4,
+                texture_info: 0,
+                styles: [255; 4],
+                lightmap_offset: u32::MAX,
+            }],
+            entity_errors: vec![],
+            original_lumps: vec![],
+        };
+
+        let map = decompile(&bsp);
+        assert_eq!(map.entities.len(), 2);
+
+        let world = &map.entities[0];
+        assert_eq!(world.entity.get("mapversion"), Some("220"));
+        assert_eq!(world.entity.properties.len(), 2);
+        assert_eq!(world.brushes.len(), 1);
+
+        // The bounds planes around the model don't touch the box
+        let faces = &world.brushes[0].faces;
+        assert_eq!(faces.len(), 6);
+
+        for (face, (normal, dist)) in faces.iter().zip(planes.iter()) {
+            let (face_normal, face_dist) = face.plane();
+
+            assert!(face_normal.dot(normal.as_f64()) > 0.999999);
+            assert!((face_dist - *dist as f64).abs() < 1e-6);
+        }
+
+        let top = &faces[4];
+        assert_eq!(top.texture, "CRATE");
+        assert_eq!(top.u_axis, DVec3::X);
+        assert_eq!(top.scale, [2.0, 1.0]);
+        assert!(faces
+            .iter()
+            .filter(|face| face.texture != "CRATE")
+            .all(|face| face.texture == "NULL"));
+
+        // The door is moved back around its origin, which gets its brush back
+        let door = &map.entities[1];
+        assert_eq!(door.entity.get("model"), None);
+        assert_eq!(door.brushes.len(), 2);
+        assert!((door.brushes[0].faces[4].plane().1 - 164.0).abs() < 1e-6);
+        assert_eq!(door.brushes[1].faces[0].texture, "ORIGIN");
+
+        // 32 units along X is 16 texels at a scale of 2
+        let door_top = &door.brushes[0].faces[4];
+        assert_eq!(door_top.texture, "CRATE");
+        assert_eq!((door_top.u_shift, door_top.v_shift), (4.0 - 16.0, 0.0));
+
+        let text = serialize(&map);
+        assert!(text.starts_with("{\n\"classname\" \"worldspawn\"\n\"mapversion\" \"220\"\n{\n"));
+        assert!(text.contains(" CRATE [ 1 0 0 4 ] [ 0 -1 0 0 ] 0 2 1\n"));
+    }
+}
@@ -0,0 +1,151 @@
+use glam::DVec3;
+
+use crate::entity::Entity;
+
+mod decompile;
+mod parse;
+mod winding;
+
+pub use decompile::decompile;
+pub use parse::parse;
+
+/// Source of a map, as level editors save it and the compile tools read it
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Map {
+    pub entities: Vec<MapEntity>,
+}
+
+/// An entity and the brushes that make up its model, only worldspawn and
+/// brush entities have brushes
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MapEntity {
+    pub entity: Entity,
+    pub brushes: Vec<Brush>,
+}
+
+/// A convex solid, the space behind all of its faces' planes
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Brush {
+    pub faces: Vec<BrushFace>,
+}
+
+impl Brush {
+    /// The polygon of each face, clipped by the brush's other planes and
+    /// clockwise seen from outside. Faces that don't touch the brush, like
+    /// repeated planes, get an empty winding
+    pub fn windings(&self) -> Vec<Vec<DVec3>> {
+        let planes = self.faces.iter().map(BrushFace::plane).collect::<Vec<_>>();
+
+        winding::brush_windings(&planes)
+    }
+}
+
+/// A brush plane through three points, clockwise seen from outside the
+/// brush, with its texture projected along Valve 220 axes
+#[derive(Debug, Clone, PartialEq)]
+pub struct BrushFace {
+    pub points: [DVec3; 3],
+    pub texture: String,
+    pub u_axis: DVec3,
+    pub u_shift: f64,
+    pub v_axis: DVec3,
+    pub v_shift: f64,
+    pub rotation: f64,
+    pub scale: [f64; 2],
+}
+
+impl BrushFace {
+    /// Unit normal pointing out of the brush, and distance from the origin
+    pub fn plane(&self) -> (DVec3, f64) {
+        let [p0, p1, p2] = self.points;
+        let normal = (p0 - p1).cross(p2 - p1).normalize();
+
+        (normal, normal.dot(p1))
+    }
+}
+
+/// Texture axes the compile tools give a standard format face, those of the
+/// floor, ceiling or wall its normal is closest to
+pub(crate) fn default_texture_axes(normal: DVec3) -> (DVec3, DVec3) {
+    const BASE_AXES: [[[f64; 3]; 3]; 6] = [
+        [[0.0, 0.0, 1.0], [1.0, 0.0, 0.0], [0.0, -1.0, 0.0]],
+        [[0.0, 0.0, -1.0], [1.0, 0.0, 0.0], [0.0, -1.0, 0.0]],
+        [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, -1.0]],
+        [[-1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, -1.0]],
+        [[0.0, 1.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, -1.0]],
+        [[0.0, -1.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, -1.0]],
+    ];
+
+    // The first of equally close axes wins
+    let mut best = (0, 0.0);
+    for (idx, [axis, _, _]) in BASE_AXES.iter().enumerate() {
+        let dot = normal.dot(DVec3::from(*axis));
+
+        if dot > best.1 {
+            best = (idx, dot);
+        }
+    }
+
+    let [_, u, v] = BASE_AXES[best.0];
+
+    (DVec3::from(u), DVec3::from(v))
+}
+
+/// Writes a map as Valve 220 `.map` text, worldspawn should have
+/// `"mapversion" "220"` for the compile tools to read it as such
+pub fn serialize(map: &Map) -> String {
+    let mut text = String::new();
+
+    for map_entity in map.entities.iter() {
+        text.push_str("{\n");
+
+        for (key, value) in map_entity.entity.properties.iter() {
+            text.push_str(&format!("\"{}\" \"{}\"\n", key, value));
+        }
+
+        for brush in map_entity.brushes.iter() {
+            text.push_str("{\n");
+
+            for face in brush.faces.iter() {
+                for point in face.points.iter() {
+                    text.push_str(&format!(
+                        "( {} {} {} ) ",
+                        number(point.x),
+                        number(point.y),
+                        number(point.z)
+                    ));
+                }
+
+                text.push_str(&format!(
+                    "{} [ {} {} {} {} ] [ {} {} {} {} ] {} {} {}\n",
+                    face.texture,
+                    number(face.u_axis.x),
+                    number(face.u_axis.y),
+                    number(face.u_axis.z),
+                    number(face.u_shift),
+                    number(face.v_axis.x),
+                    number(face.v_axis.y),
+                    number(face.v_axis.z),
+                    number(face.v_shift),
+                    number(face.rotation),
+                    number(face.scale[0]),
+                    number(face.scale[1]),
+                ));
+            }
+
+            text.push_str("}\n");
+        }
+
+        text.push_str("}\n");
+    }
+
+    text
+}
+
+/// Rounds to 6 decimals so float noise doesn't end up in the text
+fn number(value: f64) -> String {
+    let rounded = (value * 1e6).round() / 1e6;
+
+    // Adding zero writes -0 as 0
+    format!("{}", rounded + 0.0)
+}
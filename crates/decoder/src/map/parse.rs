@@ -0,0 +1,459 @@
+use std::iter::Peekable;
+use std::str::Chars;
+
+use glam::DVec3;
+
+use super::{default_texture_axes, Brush, BrushFace, Map, MapEntity};
+use crate::{Error, Result};
+
+/// Parses `.map` source, faces in the standard format get the Valve 220 axes
+/// the compile tools would project their texture along
+pub fn parse(text: &str) -> Result<Map> {
+    let mut parser = Parser {
+        lexer: Lexer {
+            chars: text.chars().peekable(),
+            line: 1,
+            column: 1,
+        },
+    };
+
+    let mut entities = vec![];
+
+    while let Some(token) = parser.lexer.next()? {
+        parser.symbol(&token, "{")?;
+
+        entities.push(parser.entity()?);
+    }
+
+    Ok(Map { entities })
+}
+
+#[derive(Debug)]
+struct Token {
+    text: String,
+    quoted: bool,
+    line: usize,
+    column: usize,
+}
+
+impl Token {
+    fn error(&self, message: String) -> Error {
+        Error::InvalidMap {
+            line: self.line,
+            column: self.column,
+            message,
+        }
+    }
+
+    fn describe(&self) -> String {
+        if self.quoted {
+            format!("\"{}\"", self.text)
+        } else {
+            format!("`{}`", self.text)
+        }
+    }
+}
+
+/// Splits text into whitespace separated words and quoted strings, skipping
+/// `//` comments
+struct Lexer<'a> {
+    chars: Peekable<Chars<'a>>,
+    line: usize,
+    column: usize,
+}
+
+impl Lexer<'_> {
+    fn bump(&mut self) -> Option<char> {
+        let c = self.chars.next()?;
+
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+
+        Some(c)
+    }
+
+    fn next(&mut self) -> Result<Option<Token>> {
+        loop {
+            while self.chars.peek().map(|c| c.is_whitespace()) == Some(true) {
+                self.bump();
+            }
+
+            let mut ahead = self.chars.clone();
+
+            if (ahead.next(), ahead.next()) != (Some('/'), Some('/')) {
+                break;
+            }
+
+            while !matches!(self.chars.peek(), None | Some('\n')) {
+                self.bump();
+            }
+        }
+
+        let (line, column) = (self.line, self.column);
+
+        let quoted = match self.chars.peek() {
+            None => return Ok(None),
+            Some('"') => {
+                self.bump();
+                true
+            }
+            Some(_) => false,
+        };
+
+        let mut text = String::new();
+
+        loop {
+            match self.chars.peek().copied() {
+                Some('"') if quoted => {
+                    self.bump();
+                    break;
+                }
+                None | Some('\n') if quoted => {
+                    return Err(Error::InvalidMap {
+                        line,
+                        column,
+                        message: "unterminated string".to_string(),
+                    });
+                }
+                None => break,
+                Some(c) if !quoted && c.is_whitespace() => break,
+                Some(c) => {
+                    self.bump();
+                    text.push(c);
+                }
+            }
+        }
+
+        Ok(Some(Token {
+            text,
+            quoted,
+            line,
+            column,
+        }))
+    }
+}
+
+struct Parser<'a> {
+    lexer: Lexer<'a>,
+}
+
+impl Parser<'_> {
+    /// The next token, or an error at the end of the text naming what was
+    /// expected there
+    fn expect(&mut self, expected: &str) -> Result<Token> {
+        self.lexer.next()?.ok_or_else(|| Error::InvalidMap {
+            line: self.lexer.line,
+            column: self.lexer.column,
+            message: format!("expected {}, found the end of the file", expected),
+        })
+    }
+
+    fn symbol(&self, token: &Token, symbol: &str) -> Result<()> {
+        if token.quoted || token.text != symbol {
+            Err(token.error(format!("expected `{}`, found {}", symbol, token.describe())))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn expect_symbol(&mut self, symbol: &str) -> Result<()> {
+        let token = self.expect(&format!("`{}`", symbol))?;
+
+        self.symbol(&token, symbol)
+    }
+
+    fn number(&mut self) -> Result<f64> {
+        let token = self.expect("a number")?;
+
+        match token.text.parse::<f64>() {
+            Ok(number) if !token.quoted && number.is_finite() => Ok(number),
+            _ => Err(token.error(format!("expected a number, found {}", token.describe()))),
+        }
+    }
+
+    fn entity(&mut self) -> Result<MapEntity> {
+        let mut entity = MapEntity::default();
+
+        loop {
+            let token = self.expect("a key, a brush or `}`")?;
+
+            if token.quoted {
+                let value = self.expect("a value")?;
+
+                if !value.quoted {
+                    return Err(value.error(format!(
+                        "expected a quoted value for key \"{}\", found {}",
+                        token.text,
+                        value.describe()
+                    )));
+                }
+
+                entity.entity.properties.push((token.text, value.text));
+
+                continue;
+            }
+
+            match token.text.as_str() {
+                "{" => entity.brushes.push(self.brush()?),
+                "}" => return Ok(entity),
+                _ => {
+                    return Err(token.error(format!(
+                        "expected a key, a brush or `}}`, found {}",
+                        token.describe()
+                    )))
+                }
+            }
+        }
+    }
+
+    fn brush(&mut self) -> Result<Brush> {
+        let mut brush = Brush::default();
+
+        loop {
+            let token = self.expect("a face or `}`")?;
+
+            match token.text.as_str() {
+                "(" if !token.quoted => brush.faces.push(self.face(&token)?),
+                "}" if !token.quoted => return Ok(brush),
+                _ => {
+                    return Err(token.error(format!(
+                        "expected a face or `}}`, found {}",
+                        token.describe()
+                    )))
+                }
+            }
+        }
+    }
+
+    /// A face, after the `(` opening its first point
+    fn face(&mut self, start: &Token) -> Result<BrushFace> {
+        let mut points = [DVec3::ZERO; 3];
+
+        for (idx, point) in points.iter_mut().enumerate() {
+            if idx > 0 {
+                self.expect_symbol("(")?;
+            }
+
+            *point = DVec3::new(self.number()?, self.number()?, self.number()?);
+
+            self.expect_symbol(")")?;
+        }
+
+        let [p0, p1, p2] = points;
+        let normal = (p0 - p1).cross(p2 - p1);
+
+        if normal.length() < 1e-6 {
+            return Err(start.error("face points are in a line".to_string()));
+        }
+
+        let texture = self.expect("a texture name")?;
+        let next = self.expect("texture axes or offsets")?;
+
+        let mut face = if next.text == "[" && !next.quoted {
+            // Valve 220, [ ux uy uz shift ] [ vx vy vz shift ] rotation scale
+            let u_axis = DVec3::new(self.number()?, self.number()?, self.number()?);
+            let u_shift = self.number()?;
+            self.expect_symbol("]")?;
+
+            self.expect_symbol("[")?;
+            let v_axis = DVec3::new(self.number()?, self.number()?, self.number()?);
+            let v_shift = self.number()?;
+            self.expect_symbol("]")?;
+
+            BrushFace {
+                points,
+                texture: texture.text,
+                u_axis,
+                u_shift,
+                v_axis,
+                v_shift,
+                rotation: self.number()?,
+                scale: [self.number()?, self.number()?],
+            }
+        } else {
+            // Standard, shift rotation scale along the closest world axes
+            let u_shift = next
+                .text
+                .parse::<f64>()
+                .ok()
+                .filter(|shift| !next.quoted && shift.is_finite())
+                .ok_or_else(|| {
+                    next.error(format!(
+                        "expected texture axes or offsets, found {}",
+                        next.describe()
+                    ))
+                })?;
+            let v_shift = self.number()?;
+            let rotation = self.number()?;
+
+            let (u_axis, v_axis) = rotate_axes(default_texture_axes(normal.normalize()), rotation);
+
+            BrushFace {
+                points,
+                texture: texture.text,
+                u_axis,
+                u_shift,
+                v_axis,
+                v_shift,
+                rotation,
+                scale: [self.number()?, self.number()?],
+            }
+        };
+
+        // The compile tools treat a scale of 0 as 1
+        for scale in face.scale.iter_mut() {
+            if *scale == 0.0 {
+                *scale = 1.0;
+            }
+        }
+
+        Ok(face)
+    }
+}
+
+/// Turns standard format axes by `rotation` degrees within the plane of the
+/// two world axes they lie along, like the compile tools do
+fn rotate_axes((u_axis, v_axis): (DVec3, DVec3), rotation: f64) -> (DVec3, DVec3) {
+    // Right angles are exact
+    let (sin, cos) = if rotation == 0.0 {
+        (0.0, 1.0)
+    } else if rotation == 90.0 {
+        (1.0, 0.0)
+    } else if rotation == 180.0 {
+        (0.0, -1.0)
+    } else if rotation == 270.0 {
+        (-1.0, 0.0)
+    } else {
+        rotation.to_radians().sin_cos()
+    };
+
+    let component = |axis: DVec3| {
+        if axis.x != 0.0 {
+            0
+        } else if axis.y != 0.0 {
+            1
+        } else {
+            2
+        }
+    };
+    let (s, t) = (component(u_axis), component(v_axis));
+
+    let rotate = |axis: DVec3| {
+        let mut rotated = <[f64; 3]>::from(axis);
+        rotated[s] = cos * axis[s] - sin * axis[t];
+        rotated[t] = sin * axis[s] + cos * axis[t];
+
+        DVec3::from(rotated)
+    };
+
+    (rotate(u_axis), rotate(v_axis))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::map::serialize;
+
+    const MAP: &str = r#"// Game: Half-Life
+{
+"classname" "worldspawn"
+"mapversion" "220"
+{
+( 0 0 64 ) ( 0 1 64 ) ( 1 0 64 ) CRATE [ 1 0 0 4 ] [ 0 -1 0 0 ] 0 2 1
+( 0 0 0 ) ( 1 0 0 ) ( 0 1 0 ) CRATE [ 1 0 0 0 ] [ 0 -1 0 0 ] 0 1 1
+( 16 0 0 ) ( 16 0 1 ) ( 16 1 0 ) CRATE [ 0 1 0 0 ] [ 0 0 -1 0 ] 0 1 1
+( -16 0 0 ) ( -16 1 0 ) ( -16 0 1 ) CRATE [ 0 1 0 0 ] [ 0 0 -1 0 ] 0 1 1
+( 0 16 0 ) ( 1 16 0 ) ( 0 16 1 ) CRATE [ 1 0 0 0 ] [ 0 0 -1 0 ] 0 1 1
+( 0 -16 0 ) ( 0 -16 1 ) ( 1 -16 0 ) CRATE [ 1 0 0 0 ] [ 0 0 -1 0 ] 0 1 1
+}
+}
+{
+"classname" "func_wall"
+{
+( 0 0 64 ) ( 0 1 64 ) ( 1 0 64 ) {grate 8 0 90 0 0.5
+( 0 0 0 ) ( 1 0 0 ) ( 0 1 0 ) {grate 0 0 0 1 1
+( 16 0 0 ) ( 16 0 1 ) ( 16 1 0 ) {grate 0 0 0 1 1
+( -16 0 0 ) ( -16 1 0 ) ( -16 0 1 ) {grate 0 0 0 1 1
+( 0 16 0 ) ( 1 16 0 ) ( 0 16 1 ) {grate 0 0 0 1 1
+( 0 -16 0 ) ( 0 -16 1 ) ( 1 -16 0 ) {grate 0 0 0 1 1
+( 0 -16 0 ) ( 0 -16 1 ) ( 1 -16 0 ) {grate 0 0 0 1 1
+}
+}
+"#;
+
+    #[test]
+    fn test_parse_formats() {
+        let map = parse(MAP).unwrap();
+        assert_eq!(map.entities.len(), 2);
+
+        let world = &map.entities[0];
+        assert_eq!(world.entity.get("mapversion"), Some("220"));
+
+        let top = &world.brushes[0].faces[0];
+        assert_eq!(top.plane(), (DVec3::Z, 64.0));
+        assert_eq!(top.texture, "CRATE");
+        assert_eq!((top.u_axis, top.u_shift), (DVec3::X, 4.0));
+        assert_eq!(top.scale, [2.0, 1.0]);
+
+        let windings = world.brushes[0].windings();
+        assert!(windings.iter().all(|winding| winding.len() == 4));
+        assert!(windings[0]
+            .iter()
+            .all(|p| p.z == 64.0 && p.x.abs() == 16.0 && p.y.abs() == 16.0));
+
+        // Standard floor axes turned a quarter, a scale of 0 is 1
+        let wall = &map.entities[1];
+        let top = &wall.brushes[0].faces[0];
+        assert_eq!(top.texture, "{grate");
+        assert_eq!((top.u_axis, top.v_axis), (DVec3::Y, DVec3::X));
+        assert_eq!(
+            (top.u_shift, top.rotation, top.scale),
+            (8.0, 90.0, [1.0, 0.5])
+        );
+
+        // The repeated plane only gets the first winding
+        let windings = wall.brushes[0].windings();
+        assert_eq!(windings[5].len(), 4);
+        assert!(windings[6].is_empty());
+
+        assert_eq!(parse(&serialize(&map)).unwrap(), map);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let error = |text: &str| match parse(text) {
+            Err(Error::InvalidMap {
+                line,
+                column,
+                message,
+            }) => (line, column, message),
+            result => panic!("expected an error, got {:?}", result),
+        };
+
+        assert_eq!(
+            error("{\n\"classname\" \"worldspawn\"\n{\n( 0 0 x ) ( 1 0 0 ) ( 0 1 0 ) A 0 0 0 1 1\n}\n}"),
+            (4, 7, "expected a number, found `x`".to_string())
+        );
+        assert_eq!(
+            error("{\n\"classname\" \"worldspawn\n}"),
+            (2, 13, "unterminated string".to_string())
+        );
+        assert_eq!(
+            error("{\n{\n( 0 0 0 ) ( 1 0 0 ) ( 2 0 0 ) A 0 0 0 1 1\n}\n}"),
+            (3, 1, "face points are in a line".to_string())
+        );
+        assert_eq!(
+            error("{\n\"classname\" \"worldspawn\"\n"),
+            (
+                3,
+                1,
+                "expected a key, a brush or `}`, found the end of the file".to_string()
+            )
+        );
+        assert_eq!(error("}"), (1, 1, "expected `{`, found `}`".to_string()));
+    }
+}
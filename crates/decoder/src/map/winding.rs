@@ -0,0 +1,148 @@
+use glam::DVec3;
+
+/// Points closer to a plane than this are treated as on it
+pub(crate) const ON_EPSILON: f64 = 0.01;
+
+/// Half the size of the polygon a plane starts as, beyond any map's extents
+const BOGUS_RANGE: f64 = 65536.0;
+
+/// A huge square on the plane, clockwise seen from the front like the
+/// compile tools' base windings
+pub(crate) fn base_winding(normal: DVec3, dist: f64) -> Vec<DVec3> {
+    let abs = normal.abs();
+
+    let up = if abs.z >= abs.x && abs.z >= abs.y {
+        DVec3::X
+    } else {
+        DVec3::Z
+    };
+
+    let up = (up - normal * up.dot(normal)).normalize() * BOGUS_RANGE;
+    let right = up.cross(normal).normalize() * BOGUS_RANGE;
+    let origin = normal * dist;
+
+    vec![
+        origin - right + up,
+        origin + right + up,
+        origin + right - up,
+        origin - right - up,
+    ]
+}
+
+/// The part of a winding behind a plane, empty if none of it is
+pub(crate) fn clip_back(winding: &[DVec3], normal: DVec3, dist: f64) -> Vec<DVec3> {
+    let distances = winding
+        .iter()
+        .map(|point| normal.dot(*point) - dist)
+        .collect::<Vec<_>>();
+
+    if distances.iter().all(|d| *d <= ON_EPSILON) {
+        return winding.to_vec();
+    }
+
+    if distances.iter().all(|d| *d >= -ON_EPSILON) {
+        return vec![];
+    }
+
+    let mut clipped = Vec::with_capacity(winding.len() + 1);
+
+    for idx in 0..winding.len() {
+        let next = (idx + 1) % winding.len();
+        let (point, distance) = (winding[idx], distances[idx]);
+        let next_distance = distances[next];
+
+        if distance <= ON_EPSILON {
+            clipped.push(point);
+        }
+
+        // Edges crossing the plane are split where they cross it
+        if (distance < -ON_EPSILON && next_distance > ON_EPSILON)
+            || (distance > ON_EPSILON && next_distance < -ON_EPSILON)
+        {
+            let t = distance / (distance - next_distance);
+            clipped.push(point + (winding[next] - point) * t);
+        }
+    }
+
+    clipped
+}
+
+/// The polygon of each plane of a convex solid, clipped by all the others.
+/// Planes that don't touch the solid get an empty winding
+pub(crate) fn brush_windings(planes: &[(DVec3, f64)]) -> Vec<Vec<DVec3>> {
+    planes
+        .iter()
+        .enumerate()
+        .map(|(idx, &(normal, dist))| {
+            let mut winding = base_winding(normal, dist);
+
+            for (other, &(other_normal, other_dist)) in planes.iter().enumerate() {
+                if other == idx || winding.is_empty() {
+                    continue;
+                }
+
+                // The same plane twice only keeps the first
+                if other_normal.dot(normal) > 1.0 - 1e-9 && (other_dist - dist).abs() < ON_EPSILON {
+                    if other < idx {
+                        winding.clear();
+                    }
+
+                    continue;
+                }
+
+                winding = clip_back(&winding, other_normal, other_dist);
+            }
+
+            if area(&winding) < ON_EPSILON {
+                winding.clear();
+            }
+
+            winding
+        })
+        .collect()
+}
+
+pub(crate) fn area(winding: &[DVec3]) -> f64 {
+    let mut total = DVec3::ZERO;
+
+    for idx in 2..winding.len() {
+        total += (winding[idx - 1] - winding[0]).cross(winding[idx] - winding[0]);
+    }
+
+    total.length() / 2.0
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_brush_windings() {
+        let planes = [
+            (DVec3::X, 16.0),
+            (-DVec3::X, 16.0),
+            (DVec3::Y, 16.0),
+            (-DVec3::Y, 16.0),
+            (DVec3::Z, 64.0),
+            (-DVec3::Z, 0.0),
+            // Outside the box, touches nothing
+            (DVec3::new(1.0, 1.0, 0.0).normalize(), 100.0),
+        ];
+
+        let windings = brush_windings(&planes);
+
+        assert!(windings[6].is_empty());
+
+        for (winding, (normal, dist)) in windings.iter().zip(planes.iter()).take(6) {
+            assert_eq!(winding.len(), 4);
+            assert!(winding.iter().all(|p| (p.dot(*normal) - dist).abs() < 1e-6));
+
+            // Clockwise from the front, so the Quake point order gives the normal
+            let derived = (winding[0] - winding[1]).cross(winding[2] - winding[1]);
+            assert!(derived.normalize().dot(*normal) > 0.999);
+        }
+
+        assert!((area(&windings[4]) - 32.0 * 32.0).abs() < 1e-6);
+        assert!((area(&windings[0]) - 32.0 * 64.0).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,397 @@
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+
+use crate::common::read_array_u8;
+use crate::{ByteDecoder, ByteEncoder, Error, Result};
+
+/// Length of an entry's file name, including the null terminator
+pub const MAXPAKNAME: usize = 56;
+
+/// Directory of a Quake / Half-Life `.pak` archive
+#[derive(Clone, Debug)]
+pub struct Pak {
+    pub entries: Vec<PakEntry>,
+}
+
+impl Pak {
+    /// Finds an entry by path, in any case and with `/` or `\` separators
+    pub fn find(&self, name: &str) -> Option<&PakEntry> {
+        let name = normalize(name);
+
+        self.entries
+            .iter()
+            .find(|entry| normalize(&entry.name) == name)
+    }
+}
+
+/// A file stored in a pak, `name` is the path inside the archive
+#[derive(Clone, Debug, PartialEq)]
+pub struct PakEntry {
+    pub name: String,
+    pub offset: u32,
+    pub size: u32,
+}
+
+impl PakEntry {
+    /// Reads the entry's contents from the pak it was listed from
+    pub fn read<R: Read + Seek>(&self, reader: &mut R) -> Result<Vec<u8>> {
+        let mut data = vec![];
+
+        self.open(reader)?.read_to_end(&mut data)?;
+
+        Ok(data)
+    }
+
+    /// Streams the entry out of the pak it was listed from, so decoders can
+    /// read it without extracting it
+    pub fn open<R: Read + Seek>(&self, reader: R) -> Result<PakFile<R>> {
+        PakFile::new(reader, self.offset as u64, self.size as u64)
+    }
+}
+
+/// `Read + Seek` view of a single file inside a pak
+#[derive(Debug)]
+pub struct PakFile<R: Read + Seek> {
+    reader: R,
+    offset: u64,
+    size: u64,
+    position: u64,
+}
+
+impl<R: Read + Seek> PakFile<R> {
+    fn new(mut reader: R, offset: u64, size: u64) -> Result<Self> {
+        reader.seek(SeekFrom::Start(offset))?;
+
+        Ok(PakFile {
+            reader,
+            offset,
+            size,
+            position: 0,
+        })
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+impl<R: Read + Seek> Read for PakFile<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let remaining = self.size.saturating_sub(self.position) as usize;
+        let len = buf.len().min(remaining);
+
+        let read = self.reader.read(&mut buf[..len])?;
+        self.position += read as u64;
+
+        Ok(read)
+    }
+}
+
+impl<R: Read + Seek> Seek for PakFile<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(position) => Some(position),
+            SeekFrom::End(delta) => checked_offset(self.size, delta),
+            SeekFrom::Current(delta) => checked_offset(self.position, delta),
+        };
+
+        let position = position.ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )
+        })?;
+
+        self.reader.seek(SeekFrom::Start(self.offset + position))?;
+        self.position = position;
+
+        Ok(position)
+    }
+}
+
+fn checked_offset(base: u64, delta: i64) -> Option<u64> {
+    if delta < 0 {
+        base.checked_sub(delta.unsigned_abs())
+    } else {
+        base.checked_add(delta as u64)
+    }
+}
+
+/// Lowercase with `/` separators, how the engine compares pak paths
+fn normalize(name: &str) -> String {
+    name.replace('\\', "/").to_lowercase()
+}
+
+pub(crate) fn decode<R: Read + Seek>(reader: &mut R) -> Result<Pak> {
+    let header = Header::decode(reader)?;
+
+    if &header.ident != b"PACK" {
+        return Err(Error::InvalidPakFormat);
+    }
+
+    let num_entries = header.dir_length as usize / DirEntry::SIZE;
+    let mut entries = Vec::with_capacity(num_entries);
+
+    reader.seek(SeekFrom::Start(header.dir_offset as u64))?;
+
+    for _ in 0..num_entries {
+        let entry = DirEntry::decode(reader)?;
+
+        let name = String::from_utf8_lossy(&entry.name).to_string();
+        let name = name.split('\0').next().unwrap_or_default().to_string();
+
+        entries.push(PakEntry {
+            name,
+            offset: entry.offset,
+            size: entry.size,
+        });
+    }
+
+    Ok(Pak { entries })
+}
+
+#[derive(Debug)]
+pub(crate) struct Encoder<W: Write + Seek> {
+    writer: W,
+    start: u64,
+    entries: Vec<PakEntry>,
+}
+
+impl<W: Write + Seek> Encoder<W> {
+    pub(crate) fn new(mut writer: W) -> Result<Self> {
+        let start = writer.stream_position()?;
+
+        // Directory location is filled in by `finish`
+        Header {
+            ident: *b"PACK",
+            dir_offset: 0,
+            dir_length: 0,
+        }
+        .encode(&mut writer)?;
+
+        Ok(Encoder {
+            writer,
+            start,
+            entries: vec![],
+        })
+    }
+
+    pub(crate) fn add(&mut self, name: &str, data: &[u8]) -> Result<()> {
+        let name = name.replace('\\', "/");
+
+        if name.is_empty() || name.len() >= MAXPAKNAME {
+            return Err(Error::Custom(format!(
+                "Pak entry name must be 1 to {} bytes: {}",
+                MAXPAKNAME - 1,
+                name
+            )));
+        }
+
+        if self
+            .entries
+            .iter()
+            .any(|entry| normalize(&entry.name) == normalize(&name))
+        {
+            return Err(Error::Custom(format!("Duplicate pak entry: {}", name)));
+        }
+
+        let offset = self.offset()?;
+        self.writer.write_all(data)?;
+
+        self.entries.push(PakEntry {
+            name,
+            offset,
+            size: data.len() as u32,
+        });
+
+        Ok(())
+    }
+
+    pub(crate) fn finish(mut self) -> Result<W> {
+        let dir_offset = self.offset()?;
+
+        for entry in self.entries.iter() {
+            let mut name = [0; MAXPAKNAME];
+            name[..entry.name.len()].copy_from_slice(entry.name.as_bytes());
+
+            DirEntry {
+                name,
+                offset: entry.offset,
+                size: entry.size,
+            }
+            .encode(&mut self.writer)?;
+        }
+
+        let end = self.writer.stream_position()?;
+
+        self.writer.seek(SeekFrom::Start(self.start))?;
+        Header {
+            ident: *b"PACK",
+            dir_offset,
+            dir_length: (self.entries.len() * DirEntry::SIZE) as u32,
+        }
+        .encode(&mut self.writer)?;
+        self.writer.seek(SeekFrom::Start(end))?;
+
+        Ok(self.writer)
+    }
+
+    /// Offset from the start of the pak, which may not be the start of `writer`
+    fn offset(&mut self) -> Result<u32> {
+        let offset = self.writer.stream_position()? - self.start;
+
+        if offset > u32::MAX as u64 {
+            return Err(Error::Custom("Pak larger than 4 GiB".to_string()));
+        }
+
+        Ok(offset as u32)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Header {
+    ident: [u8; 4],
+    dir_offset: u32,
+    dir_length: u32,
+}
+
+impl ByteEncoder for Header {
+    fn encode<W: Write>(&self, writer: &mut W) -> Result<()> {
+        writer.write_all(&self.ident)?;
+        writer.write_u32::<LittleEndian>(self.dir_offset)?;
+        writer.write_u32::<LittleEndian>(self.dir_length)?;
+
+        Ok(())
+    }
+}
+
+impl ByteDecoder for Header {
+    type Output = Header;
+
+    fn decode<R: Read + Seek>(reader: &mut R) -> Result<Self::Output> {
+        Ok(Self {
+            ident: read_array_u8(reader)?,
+            dir_offset: reader.read_u32::<LittleEndian>()?,
+            dir_length: reader.read_u32::<LittleEndian>()?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct DirEntry {
+    name: [u8; MAXPAKNAME],
+    offset: u32,
+    size: u32,
+}
+
+impl DirEntry {
+    const SIZE: usize = MAXPAKNAME + 8;
+}
+
+impl ByteEncoder for DirEntry {
+    fn encode<W: Write>(&self, writer: &mut W) -> Result<()> {
+        writer.write_all(&self.name)?;
+        writer.write_u32::<LittleEndian>(self.offset)?;
+        writer.write_u32::<LittleEndian>(self.size)?;
+
+        Ok(())
+    }
+}
+
+impl ByteDecoder for DirEntry {
+    type Output = DirEntry;
+
+    fn decode<R: Read + Seek>(reader: &mut R) -> Result<Self::Output> {
+        Ok(Self {
+            name: read_array_u8(reader)?,
+            offset: reader.read_u32::<LittleEndian>()?,
+            size: reader.read_u32::<LittleEndian>()?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::{BspDecoder, WadDecoder, WadEncoder};
+
+    #[test]
+    fn test_pak_round_trip() {
+        let mut encoder = Encoder::new(Cursor::new(vec![])).unwrap();
+        encoder.add("maps/test.bsp", b"map data").unwrap();
+        encoder.add("gfx\\env\\skyup.tga", b"sky").unwrap();
+        assert!(encoder.add("MAPS/TEST.BSP", b"again").is_err());
+
+        let mut reader = encoder.finish().unwrap();
+        reader.set_position(0);
+
+        let pak = decode(&mut reader).unwrap();
+        assert_eq!(pak.entries.len(), 2);
+
+        let entry = pak.find("GFX/ENV/SKYUP.TGA").unwrap();
+        assert_eq!(entry.name, "gfx/env/skyup.tga");
+        assert_eq!(entry.read(&mut reader).unwrap(), b"sky");
+
+        let mut file = pak
+            .find("maps/test.bsp")
+            .unwrap()
+            .open(&mut reader)
+            .unwrap();
+        let mut data = String::new();
+
+        file.seek(SeekFrom::End(-4)).unwrap();
+        file.read_to_string(&mut data).unwrap();
+        assert_eq!(data, "data");
+
+        assert_eq!(file.seek(SeekFrom::Current(-8)).unwrap(), 0);
+        assert!(file.seek(SeekFrom::Current(-1)).is_err());
+    }
+
+    #[test]
+    fn test_decode_from_pak() {
+        // A map with only entities and an empty texture lump
+        let entities = b"{\n\"classname\" \"worldspawn\"\n}\n\0";
+        let mut bsp = 30i32.to_le_bytes().to_vec();
+        for lump in 0..15 {
+            let (offset, len) = match lump {
+                0 => (128, entities.len() as i32),
+                2 => (124, 4),
+                _ => (124i32, 0),
+            };
+            bsp.extend_from_slice(&offset.to_le_bytes());
+            bsp.extend_from_slice(&len.to_le_bytes());
+        }
+        bsp.extend_from_slice(&0u32.to_le_bytes());
+        bsp.extend_from_slice(entities);
+
+        let mut wad = WadEncoder::from_writer(Cursor::new(vec![])).unwrap();
+        wad.add_texture("wall", 16, 16, &[255; 16 * 16 * 4])
+            .unwrap();
+        let wad = wad.finish().unwrap().into_inner();
+
+        // Stored after another file, so every seek has to be from the entry
+        let mut encoder = Encoder::new(Cursor::new(vec![])).unwrap();
+        encoder.add("readme.txt", b"not a map").unwrap();
+        encoder.add("maps/test.bsp", &bsp).unwrap();
+        encoder.add("test.wad", &wad).unwrap();
+        let mut reader = encoder.finish().unwrap();
+        reader.set_position(0);
+
+        let pak = decode(&mut reader).unwrap();
+
+        let entry = pak.find("maps/test.bsp").unwrap();
+        assert!(entry.offset > 12);
+        let mut decoder = BspDecoder::from_reader(entry.open(&mut reader).unwrap()).unwrap();
+        let map = decoder.decode_gold_src_30().unwrap();
+        assert_eq!(map.entities[0].classname(), Some("worldspawn"));
+
+        let entry = pak.find("test.wad").unwrap();
+        let wad = WadDecoder::from_reader(entry.open(&mut reader).unwrap())
+            .decode()
+            .unwrap();
+        assert_eq!(wad.textures["wall"].width, 16);
+    }
+}
@@ -0,0 +1,110 @@
+use std::collections::HashMap;
+
+/// Median cut palette of at most `max` colors, each weighted by how many
+/// pixels use it. Images with few enough colors keep them exactly
+pub(crate) fn palette<I: IntoIterator<Item = [u8; 3]>>(colors: I, max: usize) -> Vec<[u8; 3]> {
+    let mut histogram = HashMap::new();
+
+    for color in colors {
+        *histogram.entry(color).or_insert(0u64) += 1;
+    }
+
+    let mut colors = histogram.into_iter().collect::<Vec<_>>();
+    colors.sort_unstable();
+
+    if colors.len() <= max {
+        return colors.into_iter().map(|(color, _)| color).collect();
+    }
+
+    let mut boxes = vec![colors];
+
+    while boxes.len() < max {
+        let widest = boxes
+            .iter()
+            .enumerate()
+            .filter(|(_, colors)| colors.len() > 1)
+            .map(|(idx, colors)| (idx, widest_channel(colors)))
+            .max_by_key(|(_, (_, range))| *range);
+
+        let (idx, channel) = match widest {
+            Some((idx, (channel, range))) if range > 0 => (idx, channel),
+            _ => break,
+        };
+
+        let mut colors = boxes.swap_remove(idx);
+        colors.sort_unstable_by_key(|(color, _)| color[channel]);
+
+        // Split where half the pixels are on each side, keeping both non-empty
+        let half = colors.iter().map(|(_, count)| count).sum::<u64>() / 2;
+        let mut total = 0;
+        let split = colors
+            .iter()
+            .position(|(_, count)| {
+                total += count;
+                total > half
+            })
+            .unwrap_or_default()
+            .clamp(1, colors.len() - 1);
+
+        let upper = colors.split_off(split);
+        boxes.push(colors);
+        boxes.push(upper);
+    }
+
+    boxes.iter().map(|colors| average(colors)).collect()
+}
+
+/// Index of the closest color in `palette`, by squared distance
+pub(crate) fn nearest(palette: &[[u8; 3]], color: [u8; 3]) -> u8 {
+    let distance = |other: &[u8; 3]| {
+        other
+            .iter()
+            .zip(color.iter())
+            .map(|(&a, &b)| (a as i32 - b as i32).pow(2))
+            .sum::<i32>()
+    };
+
+    palette
+        .iter()
+        .enumerate()
+        .min_by_key(|(_, other)| distance(other))
+        .map(|(idx, _)| idx as u8)
+        .unwrap_or_default()
+}
+
+fn widest_channel(colors: &[([u8; 3], u64)]) -> (usize, u8) {
+    (0..3)
+        .map(|channel| {
+            let min = colors
+                .iter()
+                .map(|(c, _)| c[channel])
+                .min()
+                .unwrap_or_default();
+            let max = colors
+                .iter()
+                .map(|(c, _)| c[channel])
+                .max()
+                .unwrap_or_default();
+
+            (channel, max - min)
+        })
+        .max_by_key(|(_, range)| *range)
+        .unwrap_or_default()
+}
+
+fn average(colors: &[([u8; 3], u64)]) -> [u8; 3] {
+    let total = colors.iter().map(|(_, count)| count).sum::<u64>().max(1);
+    let mut sum = [0u64; 3];
+
+    for (color, count) in colors {
+        for (sum, &channel) in sum.iter_mut().zip(color.iter()) {
+            *sum += channel as u64 * count;
+        }
+    }
+
+    [
+        ((sum[0] + total / 2) / total) as u8,
+        ((sum[1] + total / 2) / total) as u8,
+        ((sum[2] + total / 2) / total) as u8,
+    ]
+}
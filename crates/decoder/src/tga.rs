@@ -0,0 +1,275 @@
+use std::io::{Read, Seek, SeekFrom};
+
+use byteorder::{LittleEndian, ReadBytesExt};
+
+use crate::common::read_array_u8;
+use crate::{ByteDecoder, Error, Result};
+
+/// Decoded image, as RGBA8 rows from top to bottom
+#[derive(Clone, Debug)]
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    pub data: Vec<u8>,
+}
+
+pub(crate) fn decode<R: Read + Seek>(reader: &mut R) -> Result<Image> {
+    let header = Header::decode(reader)?;
+
+    let (rle, kind) = match header.image_type {
+        1 => (false, ImageKind::ColorMapped),
+        2 => (false, ImageKind::TrueColor),
+        3 => (false, ImageKind::Grayscale),
+        9 => (true, ImageKind::ColorMapped),
+        10 => (true, ImageKind::TrueColor),
+        11 => (true, ImageKind::Grayscale),
+        _ => return Err(Error::InvalidTgaFormat("unsupported image type")),
+    };
+
+    reader.seek(SeekFrom::Current(header.id_length as i64))?;
+
+    let mut color_map = vec![];
+
+    if header.color_map_type == 1 {
+        for _ in 0..header.color_map_length {
+            color_map.push(read_color(reader, header.color_map_entry_size)?);
+        }
+    }
+
+    let read_pixel = |reader: &mut R| -> Result<[u8; 4]> {
+        match kind {
+            ImageKind::ColorMapped => {
+                let idx = match header.pixel_depth {
+                    8 => reader.read_u8()? as usize,
+                    16 => reader.read_u16::<LittleEndian>()? as usize,
+                    _ => return Err(Error::InvalidTgaFormat("unsupported color map index size")),
+                };
+
+                let idx = idx.wrapping_sub(header.color_map_first as usize);
+
+                color_map
+                    .get(idx)
+                    .copied()
+                    .ok_or(Error::InvalidTgaFormat("color map index out of range"))
+            }
+            ImageKind::TrueColor => read_color(reader, header.pixel_depth),
+            ImageKind::Grayscale => {
+                let value = reader.read_u8()?;
+                let alpha = if header.pixel_depth == 16 {
+                    reader.read_u8()?
+                } else {
+                    255
+                };
+
+                Ok([value, value, value, alpha])
+            }
+        }
+    };
+
+    let width = header.width as usize;
+    let height = header.height as usize;
+    let num_pixels = width * height;
+
+    // A run packet is one byte and one pixel for up to 128 pixels, sizes
+    // beyond what the rest of the file could hold are rejected before
+    // allocating for them
+    let position = reader.stream_position()?;
+    let remaining = (reader.seek(SeekFrom::End(0))? - position) as usize;
+    reader.seek(SeekFrom::Start(position))?;
+
+    let pixel_size = match header.pixel_depth {
+        0..=8 => 1,
+        9..=16 => 2,
+        17..=24 => 3,
+        _ => 4,
+    };
+    let max_pixels = if rle {
+        remaining / (pixel_size + 1) * 128
+    } else {
+        remaining / pixel_size
+    };
+
+    if num_pixels > max_pixels {
+        return Err(Error::InvalidTgaFormat("image size larger than its data"));
+    }
+
+    let mut pixels = Vec::with_capacity(num_pixels);
+
+    if rle {
+        while pixels.len() < num_pixels {
+            let packet = reader.read_u8()?;
+            let count = (packet & 0x7f) as usize + 1;
+
+            if packet & 0x80 > 0 {
+                let pixel = read_pixel(reader)?;
+
+                for _ in 0..count {
+                    pixels.push(pixel);
+                }
+            } else {
+                for _ in 0..count {
+                    pixels.push(read_pixel(reader)?);
+                }
+            }
+        }
+
+        pixels.truncate(num_pixels);
+    } else {
+        for _ in 0..num_pixels {
+            pixels.push(read_pixel(reader)?);
+        }
+    }
+
+    let right_to_left = header.descriptor & 0x10 > 0;
+    let top_to_bottom = header.descriptor & 0x20 > 0;
+
+    let mut data = Vec::with_capacity(num_pixels * 4);
+
+    for y in 0..height {
+        let row = if top_to_bottom { y } else { height - 1 - y };
+
+        for x in 0..width {
+            let column = if right_to_left { width - 1 - x } else { x };
+
+            data.extend_from_slice(&pixels[row * width + column]);
+        }
+    }
+
+    Ok(Image {
+        width: width as u32,
+        height: height as u32,
+        data,
+    })
+}
+
+fn read_color<R: Read + Seek>(reader: &mut R, bits: u8) -> Result<[u8; 4]> {
+    match bits {
+        15 | 16 => {
+            let value = reader.read_u16::<LittleEndian>()?;
+
+            let expand = |v: u16| ((v & 0x1f) << 3 | (v & 0x1f) >> 2) as u8;
+            let alpha = if bits == 16 && value & 0x8000 == 0 {
+                0
+            } else {
+                255
+            };
+
+            Ok([
+                expand(value >> 10),
+                expand(value >> 5),
+                expand(value),
+                alpha,
+            ])
+        }
+        24 => {
+            let [b, g, r] = read_array_u8(reader)?;
+
+            Ok([r, g, b, 255])
+        }
+        32 => {
+            let [b, g, r, a] = read_array_u8(reader)?;
+
+            Ok([r, g, b, a])
+        }
+        _ => Err(Error::InvalidTgaFormat("unsupported pixel depth")),
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum ImageKind {
+    ColorMapped,
+    TrueColor,
+    Grayscale,
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy)]
+struct Header {
+    id_length: u8,
+    color_map_type: u8,
+    image_type: u8,
+    color_map_first: u16,
+    color_map_length: u16,
+    color_map_entry_size: u8,
+    x_origin: u16,
+    y_origin: u16,
+    width: u16,
+    height: u16,
+    pixel_depth: u8,
+    descriptor: u8,
+}
+
+impl ByteDecoder for Header {
+    type Output = Header;
+
+    fn decode<R: Read + Seek>(reader: &mut R) -> Result<Self::Output> {
+        Ok(Self {
+            id_length: reader.read_u8()?,
+            color_map_type: reader.read_u8()?,
+            image_type: reader.read_u8()?,
+            color_map_first: reader.read_u16::<LittleEndian>()?,
+            color_map_length: reader.read_u16::<LittleEndian>()?,
+            color_map_entry_size: reader.read_u8()?,
+            x_origin: reader.read_u16::<LittleEndian>()?,
+            y_origin: reader.read_u16::<LittleEndian>()?,
+            width: reader.read_u16::<LittleEndian>()?,
+            height: reader.read_u16::<LittleEndian>()?,
+            pixel_depth: reader.read_u8()?,
+            descriptor: reader.read_u8()?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use super::*;
+
+    fn header(image_type: u8, width: u16, height: u16, pixel_depth: u8, descriptor: u8) -> Vec<u8> {
+        let mut bytes = vec![0, 0, image_type, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        bytes.extend_from_slice(&width.to_le_bytes());
+        bytes.extend_from_slice(&height.to_le_bytes());
+        bytes.extend_from_slice(&[pixel_depth, descriptor]);
+
+        bytes
+    }
+
+    #[test]
+    fn test_decode_raw() {
+        // Bottom to top, BGR
+        let mut bytes = header(2, 2, 2, 24, 0);
+        bytes.extend_from_slice(&[0, 0, 1, 0, 0, 2, 0, 0, 3, 0, 0, 4]);
+
+        let image = decode(&mut Cursor::new(bytes)).unwrap();
+
+        assert_eq!((image.width, image.height), (2, 2));
+        assert_eq!(
+            image.data,
+            vec![3, 0, 0, 255, 4, 0, 0, 255, 1, 0, 0, 255, 2, 0, 0, 255]
+        );
+    }
+
+    #[test]
+    fn test_decode_rle() {
+        // Top to bottom, BGRA, a run of two then a raw packet of one
+        let mut bytes = header(10, 3, 1, 32, 0x20);
+        bytes.extend_from_slice(&[0x81, 1, 2, 3, 4, 0x00, 5, 6, 7, 8]);
+
+        let image = decode(&mut Cursor::new(bytes)).unwrap();
+
+        assert_eq!((image.width, image.height), (3, 1));
+        assert_eq!(image.data, vec![3, 2, 1, 4, 3, 2, 1, 4, 7, 6, 5, 8]);
+    }
+
+    #[test]
+    fn test_decode_size_larger_than_data() {
+        let mut bytes = header(2, 4096, 4096, 24, 0);
+        bytes.extend_from_slice(&[0, 0, 0]);
+        assert!(decode(&mut Cursor::new(bytes)).is_err());
+
+        let mut bytes = header(10, 4096, 4096, 24, 0);
+        bytes.extend_from_slice(&[0xff, 0, 0, 0]);
+        assert!(decode(&mut Cursor::new(bytes)).is_err());
+    }
+}
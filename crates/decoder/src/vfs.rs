@@ -0,0 +1,324 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, File};
+use std::io::{self, BufReader, ErrorKind, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use crate::pak::{PakEntry, PakFile};
+use crate::{PakDecoder, Result};
+
+/// Layered view over game directories and `.pak` archives. Paths use `/` or
+/// `\` and match case-insensitively, the first mount holding a file wins
+#[derive(Debug, Default)]
+pub struct Vfs {
+    mounts: Vec<Mount>,
+}
+
+#[derive(Debug)]
+enum Mount {
+    Dir(PathBuf),
+    Pak {
+        path: PathBuf,
+        entries: HashMap<String, PakEntry>,
+    },
+}
+
+impl Vfs {
+    pub fn new() -> Self {
+        Vfs::default()
+    }
+
+    /// Mounts loose files, below everything mounted so far
+    pub fn mount_dir<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
+        let path = path.as_ref();
+
+        if !path.is_dir() {
+            return Err(not_found(&path.to_string_lossy()));
+        }
+
+        self.mounts.push(Mount::Dir(path.to_path_buf()));
+
+        Ok(())
+    }
+
+    /// Mounts a pak archive, below everything mounted so far
+    pub fn mount_pak<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
+        let path = path.as_ref();
+
+        let pak = PakDecoder::from_reader(BufReader::new(File::open(path)?)).decode()?;
+
+        let entries = pak
+            .entries
+            .into_iter()
+            .map(|entry| (normalize(&entry.name), entry))
+            .collect();
+
+        self.mounts.push(Mount::Pak {
+            path: path.to_path_buf(),
+            entries,
+        });
+
+        Ok(())
+    }
+
+    /// Mounts a game or mod directory like the engine does, its `pakN.pak`
+    /// archives from the highest number down, then its loose files
+    pub fn mount_game_dir<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
+        let path = path.as_ref();
+
+        let mut paks = fs::read_dir(path)?
+            .flatten()
+            .filter_map(|entry| {
+                let name = entry.file_name().to_string_lossy().to_lowercase();
+                let number = name.strip_prefix("pak")?.strip_suffix(".pak")?;
+
+                Some((number.parse::<u32>().ok()?, entry.path()))
+            })
+            .collect::<Vec<_>>();
+        paks.sort();
+
+        for (_, pak) in paks.iter().rev() {
+            self.mount_pak(pak)?;
+        }
+
+        self.mount_dir(path)
+    }
+
+    pub fn exists(&self, path: &str) -> bool {
+        let normalized = normalize(path);
+
+        self.mounts.iter().any(|mount| match mount {
+            Mount::Dir(root) => find_path(root, &normalized).is_some(),
+            Mount::Pak { entries, .. } => entries.contains_key(&normalized),
+        })
+    }
+
+    /// Whether any mount has files under `path`
+    pub fn is_dir(&self, path: &str) -> bool {
+        let normalized = normalize(path);
+        let prefix = format!("{}/", normalized);
+
+        normalized.is_empty()
+            || self.mounts.iter().any(|mount| match mount {
+                Mount::Dir(root) => find_path(root, &normalized)
+                    .map(|path| path.is_dir())
+                    .unwrap_or_default(),
+                Mount::Pak { entries, .. } => entries.keys().any(|name| name.starts_with(&prefix)),
+            })
+    }
+
+    /// Files and directories directly under `path`, from every mount. Names
+    /// differing only in case are listed once, names from paks are lowercase
+    pub fn read_dir(&self, path: &str) -> Vec<String> {
+        let normalized = normalize(path);
+        let prefix = if normalized.is_empty() {
+            normalized.clone()
+        } else {
+            format!("{}/", normalized)
+        };
+
+        let mut names = BTreeMap::new();
+        let mut insert = |name: String| {
+            names.entry(name.to_lowercase()).or_insert(name);
+        };
+
+        for mount in self.mounts.iter() {
+            match mount {
+                Mount::Dir(root) => {
+                    if let Some(dir) = find_path(root, &normalized) {
+                        for entry in fs::read_dir(dir).into_iter().flatten().flatten() {
+                            let name = entry.file_name().to_string_lossy().to_string();
+
+                            insert(format!("{}{}", prefix, name));
+                        }
+                    }
+                }
+                Mount::Pak { entries, .. } => {
+                    for name in entries.keys() {
+                        if let Some(rest) = name.strip_prefix(&prefix) {
+                            let child = rest.split('/').next().unwrap_or_default();
+
+                            insert(format!("{}{}", prefix, child));
+                        }
+                    }
+                }
+            }
+        }
+
+        names.values().cloned().collect()
+    }
+
+    pub fn open(&self, path: &str) -> Result<VfsFile> {
+        let normalized = normalize(path);
+
+        for mount in self.mounts.iter() {
+            match mount {
+                Mount::Dir(root) => {
+                    if let Some(path) = find_path(root, &normalized).filter(|p| p.is_file()) {
+                        return Ok(VfsFile::File(BufReader::new(File::open(path)?)));
+                    }
+                }
+                Mount::Pak { path, entries } => {
+                    if let Some(entry) = entries.get(&normalized) {
+                        let file = BufReader::new(File::open(path)?);
+
+                        return Ok(VfsFile::Pak(entry.open(file)?));
+                    }
+                }
+            }
+        }
+
+        Err(not_found(path))
+    }
+
+    pub fn read(&self, path: &str) -> Result<Vec<u8>> {
+        let mut data = vec![];
+
+        self.open(path)?.read_to_end(&mut data)?;
+
+        Ok(data)
+    }
+}
+
+/// A file opened from a [`Vfs`], usable with any of the decoders
+#[derive(Debug)]
+pub enum VfsFile {
+    File(BufReader<File>),
+    Pak(PakFile<BufReader<File>>),
+}
+
+impl Read for VfsFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            VfsFile::File(reader) => reader.read(buf),
+            VfsFile::Pak(reader) => reader.read(buf),
+        }
+    }
+}
+
+impl Seek for VfsFile {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        match self {
+            VfsFile::File(reader) => reader.seek(pos),
+            VfsFile::Pak(reader) => reader.seek(pos),
+        }
+    }
+}
+
+/// Lowercase with `/` separators and no leading or trailing separator. `..`
+/// removes the part before it and never goes above the root
+pub(crate) fn normalize(path: &str) -> String {
+    let mut parts = vec![];
+
+    for part in path.split(&['/', '\\'][..]) {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            part => parts.push(part),
+        }
+    }
+
+    parts.join("/").to_lowercase()
+}
+
+/// Finds a path below `root`, each component matching in any case
+fn find_path(root: &Path, normalized: &str) -> Option<PathBuf> {
+    let mut path = root.to_path_buf();
+
+    for part in normalized.split('/').filter(|part| !part.is_empty()) {
+        let exact = path.join(part);
+
+        path = if exact.exists() {
+            exact
+        } else {
+            fs::read_dir(&path)
+                .ok()?
+                .flatten()
+                .find(|entry| entry.file_name().to_string_lossy().to_lowercase() == part)?
+                .path()
+        };
+    }
+
+    Some(path)
+}
+
+fn not_found(path: &str) -> crate::Error {
+    io::Error::new(ErrorKind::NotFound, format!("{} not found", path)).into()
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+    use crate::PakEncoder;
+
+    static NEXT_TEMP_DIR: AtomicUsize = AtomicUsize::new(0);
+
+    /// Directory unique to the process and test, removed when dropped so a
+    /// failing test doesn't leave it behind
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> TempDir {
+            let path = std::env::temp_dir().join(format!(
+                "decoder_{}_{}_{}",
+                name,
+                std::process::id(),
+                NEXT_TEMP_DIR.fetch_add(1, Ordering::Relaxed)
+            ));
+
+            let _ = fs::remove_dir_all(&path);
+            fs::create_dir_all(&path).unwrap();
+
+            TempDir(path)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn test_vfs_lookup() {
+        let temp = TempDir::new("vfs");
+        let root = &temp.0;
+        let mod_dir = root.join("cstrike");
+        let base_dir = root.join("valve");
+
+        fs::create_dir_all(mod_dir.join("Maps")).unwrap();
+        fs::create_dir_all(base_dir.join("maps")).unwrap();
+        fs::write(mod_dir.join("Maps").join("DE_Dust.bsp"), b"mod").unwrap();
+        fs::write(base_dir.join("maps").join("de_dust.bsp"), b"base").unwrap();
+        fs::write(base_dir.join("maps").join("c1a0.bsp"), b"base").unwrap();
+
+        for (name, data) in [("pak2.pak", &b"pak2"[..]), ("PAK10.PAK", &b"pak10"[..])] {
+            let mut encoder =
+                PakEncoder::from_writer(File::create(base_dir.join(name)).unwrap()).unwrap();
+            encoder.add("liblist.gam", data).unwrap();
+            encoder.finish().unwrap();
+        }
+
+        // Not a numbered pak, so it isn't mounted
+        fs::write(base_dir.join("pak0_backup.pak"), b"not a pak").unwrap();
+
+        let mut vfs = Vfs::new();
+        vfs.mount_game_dir(&mod_dir).unwrap();
+        vfs.mount_game_dir(&base_dir).unwrap();
+
+        assert_eq!(vfs.read("maps\\de_dust.bsp").unwrap(), b"mod");
+        assert_eq!(vfs.read("MAPS/C1A0.BSP").unwrap(), b"base");
+        assert!(vfs.is_dir("maps"));
+        assert!(!vfs.exists("maps/missing.bsp"));
+        assert_eq!(vfs.read("liblist.gam").unwrap(), b"pak10");
+        assert_eq!(vfs.read("maps/../maps/c1a0.bsp").unwrap(), b"base");
+        assert!(!vfs.exists("maps/../../valve/maps/c1a0.bsp"));
+        assert_eq!(
+            vfs.read_dir("maps"),
+            vec!["maps/c1a0.bsp", "maps/DE_Dust.bsp"]
+        );
+    }
+}
@@ -1,53 +1,632 @@
 use std::collections::HashMap;
-use std::io::{Read, Seek, SeekFrom};
+use std::io::{Read, Seek, SeekFrom, Write};
 
-use byteorder::{LittleEndian, ReadBytesExt};
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 
 use crate::common::*;
-use crate::format::gold_src_30::{Texture, MAXTEXTURENAME};
-use crate::{ByteDecoder, Error, Result};
+use crate::entity::Entity;
+use crate::format::gold_src_30::{mip_offsets, Texture, MAXTEXTURENAME, MIPLEVELS};
+use crate::quantize;
+use crate::{ByteDecoder, ByteEncoder, Error, Result};
+
+/// Lump kinds found in Quake and Half-Life WADs
+pub const KIND_PALETTE: u8 = 0x40;
+pub const KIND_PICTURE: u8 = 0x42;
+pub const KIND_MIPTEX: u8 = 0x43;
+pub const KIND_QUAKE_MIPTEX: u8 = 0x44;
+pub const KIND_FONT: u8 = 0x46;
+
+pub type Palette = [[u8; 3]; 256];
 
 #[derive(Clone, Debug)]
 pub struct Wad {
+    /// Mip textures by name. Quake ones use the WAD's palette lump if it has one
     pub textures: HashMap<String, Texture>,
+    /// Every directory entry in file order, with its lump or why it was skipped
+    pub entries: Vec<WadEntry>,
 }
 
-pub(crate) fn decode<R: Read + Seek>(reader: &mut R) -> Result<Wad> {
-    let header = Header::decode(reader)?;
+#[derive(Clone, Debug)]
+pub struct WadEntry {
+    pub dir: WadDirEntry,
+    pub lump: std::result::Result<WadLump, WadSkipReason>,
+}
+
+/// An entry of the WAD directory, `kind` is one of the `KIND_` constants for
+/// lumps that can be decoded
+#[derive(Clone, Debug, PartialEq)]
+pub struct WadDirEntry {
+    pub name: String,
+    pub kind: u8,
+    pub offset: u32,
+    pub disk_size: u32,
+    pub size: u32,
+    pub compressed: bool,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum WadSkipReason {
+    /// Compression was never used by the tools, and isn't supported
+    Compressed,
+    UnknownKind,
+    Invalid(String),
+}
+
+#[derive(Clone, Debug)]
+pub enum WadLump {
+    /// Half-Life mip texture, with its own palette
+    MipTexture(Texture),
+    /// Quake mip texture, without a palette
+    QuakeMipTexture(Texture),
+    Picture(Picture),
+    Font(Font),
+    Palette(Box<Palette>),
+}
+
+/// A `qpic` image, like the HUD and menu graphics of `gfx.wad`. Quake WAD2
+/// pictures use the game palette and have none of their own
+#[derive(Clone, Debug)]
+pub struct Picture {
+    pub width: u32,
+    pub height: u32,
+    pub data: Vec<u8>,
+    pub palette: Option<Box<Palette>>,
+}
+
+/// A bitmap font from `fonts.wad`, with glyphs packed in rows of `row_height`
+#[derive(Clone, Debug)]
+pub struct Font {
+    pub width: u32,
+    pub height: u32,
+    pub row_count: u32,
+    pub row_height: u32,
+    pub chars: Vec<FontChar>,
+    pub data: Vec<u8>,
+    pub palette: Box<Palette>,
+}
+
+/// Where a glyph starts in the font's data, and how wide it is
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FontChar {
+    pub offset: u16,
+    pub width: u16,
+}
+
+/// File names of the WADs a map declares in worldspawn's `wad` key, in the
+/// order textures are searched. The key holds `;` separated paths from the
+/// mapper's machine, so only the file name is kept
+pub fn declared_wads(entities: &[Entity]) -> Vec<String> {
+    let value = entities
+        .iter()
+        .find(|e| e.classname() == Some("worldspawn"))
+        .and_then(|e| e.get("wad"))
+        .unwrap_or_default();
+
+    value
+        .split(';')
+        .filter_map(|path| path.trim().rsplit(&['/', '\\'][..]).next())
+        .filter(|name| !name.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Removes WADs from worldspawn's `wad` key, matching their file names in any
+/// case. Other paths and the trailing `;` are kept as written
+pub fn strip_declared_wads(entities: &mut [Entity], names: &[&str]) {
+    let value = entities
+        .iter_mut()
+        .filter(|e| e.classname() == Some("worldspawn"))
+        .flat_map(|e| e.properties.iter_mut())
+        .find(|(key, _)| key == "wad");
+
+    let value = match value {
+        Some((_, value)) => value,
+        None => return,
+    };
+
+    let trailing = value.trim_end().ends_with(';');
 
-    let version = String::from_utf8_lossy(&header.ident).to_string();
-    if !["WAD2", "WAD3"].contains(&version.as_str()) {
-        return Err(Error::InvalidWadFormat);
+    let mut kept = value
+        .split(';')
+        .filter(|path| !path.trim().is_empty())
+        .filter(|path| {
+            let name = path
+                .trim()
+                .rsplit(&['/', '\\'][..])
+                .next()
+                .unwrap_or_default();
+
+            !names.iter().any(|n| n.eq_ignore_ascii_case(name))
+        })
+        .collect::<Vec<_>>()
+        .join(";");
+
+    if trailing && !kept.is_empty() {
+        kept.push(';');
     }
 
-    let mut dirs = Vec::with_capacity(header.num_dirs as usize);
-    reader.seek(SeekFrom::Start(header.dir_offset as u64))?;
+    *value = kept;
+}
+
+/// The directory of a WAD and the reader it came from, lumps are only decoded
+/// when asked for. Names are looked up case-insensitively like the engine does
+#[derive(Debug)]
+pub struct WadIndex<R: Read + Seek> {
+    reader: R,
+    is_wad3: bool,
+    entries: Vec<WadDirEntry>,
+    by_name: HashMap<String, usize>,
+}
+
+impl<R: Read + Seek> WadIndex<R> {
+    pub(crate) fn new(mut reader: R) -> Result<Self> {
+        let header = Header::decode(&mut reader)?;
+
+        let version = String::from_utf8_lossy(&header.ident).to_string();
+        if !["WAD2", "WAD3"].contains(&version.as_str()) {
+            return Err(Error::InvalidWadFormat);
+        }
+
+        let mut entries = Vec::with_capacity(header.num_dirs as usize);
+        reader.seek(SeekFrom::Start(header.dir_offset as u64))?;
+
+        for _ in 0..header.num_dirs {
+            let entry = DirEntry::decode(&mut reader)?;
+
+            let name = String::from_utf8_lossy(&entry.name).to_string();
+            let name = name.split('\0').next().unwrap_or_default().to_string();
+
+            entries.push(WadDirEntry {
+                name,
+                kind: entry.kind,
+                offset: entry.offset as u32,
+                disk_size: entry.disk_size as u32,
+                size: entry.size as u32,
+                compressed: entry.compression,
+            });
+        }
+
+        let mut by_name = HashMap::new();
+        for (idx, entry) in entries.iter().enumerate() {
+            by_name.entry(entry.name.to_lowercase()).or_insert(idx);
+        }
+
+        Ok(WadIndex {
+            reader,
+            is_wad3: version == "WAD3",
+            entries,
+            by_name,
+        })
+    }
+
+    pub fn entries(&self) -> &[WadDirEntry] {
+        &self.entries
+    }
+
+    /// The first entry with this name, in any case
+    pub fn find(&self, name: &str) -> Option<&WadDirEntry> {
+        self.by_name
+            .get(&name.to_lowercase())
+            .map(|idx| &self.entries[*idx])
+    }
 
-    for _ in 0..header.num_dirs {
-        let entry = DirEntry::decode(reader)?;
+    pub fn lump(&mut self, name: &str) -> Option<std::result::Result<WadLump, WadSkipReason>> {
+        let idx = *self.by_name.get(&name.to_lowercase())?;
 
-        dirs.push(entry);
+        Some(self.decode_entry(idx))
     }
 
+    /// Decodes a single mip texture, Quake ones get the WAD's palette lump
+    pub fn texture(&mut self, name: &str) -> Option<Texture> {
+        match self.lump(name)? {
+            Ok(WadLump::MipTexture(texture)) => Some(texture),
+            Ok(WadLump::QuakeMipTexture(mut texture)) => {
+                if let Some(palette) = self.palette() {
+                    texture.palette = *palette;
+                }
+
+                Some(texture)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    fn decode_entry(&mut self, idx: usize) -> std::result::Result<WadLump, WadSkipReason> {
+        let dir = &self.entries[idx];
+
+        if dir.compressed || dir.size != dir.disk_size {
+            Err(WadSkipReason::Compressed)
+        } else {
+            decode_lump(&mut self.reader, dir, self.is_wad3)
+        }
+    }
+
+    fn palette(&mut self) -> Option<Box<Palette>> {
+        let idx = self
+            .entries
+            .iter()
+            .position(|entry| entry.kind == KIND_PALETTE)?;
+
+        match self.decode_entry(idx) {
+            Ok(WadLump::Palette(palette)) => Some(palette),
+            _ => None,
+        }
+    }
+}
+
+pub(crate) fn decode<R: Read + Seek>(reader: &mut R) -> Result<Wad> {
+    let mut index = WadIndex::new(reader)?;
+
+    let mut entries = Vec::with_capacity(index.entries.len());
+
+    for idx in 0..index.entries.len() {
+        let lump = index.decode_entry(idx);
+
+        entries.push(WadEntry {
+            dir: index.entries[idx].clone(),
+            lump,
+        });
+    }
+
+    let palette = entries.iter().find_map(|entry| match &entry.lump {
+        Ok(WadLump::Palette(palette)) => Some(palette.clone()),
+        _ => None,
+    });
+
     let mut textures = HashMap::new();
 
-    for entry in dirs.iter() {
-        let offset = entry.offset as usize;
+    for entry in entries.iter() {
+        match &entry.lump {
+            Ok(WadLump::MipTexture(texture)) => {
+                textures.insert(entry.dir.name.clone(), texture.clone());
+            }
+            Ok(WadLump::QuakeMipTexture(texture)) => {
+                let mut texture = texture.clone();
 
-        if entry.compression || entry.size != entry.disk_size || entry.kind != 0x43 {
-            continue;
+                if let Some(palette) = &palette {
+                    texture.palette = **palette;
+                }
+
+                textures.insert(entry.dir.name.clone(), texture);
+            }
+            _ => {}
         }
+    }
+
+    Ok(Wad { textures, entries })
+}
 
+fn decode_lump<R: Read + Seek>(
+    reader: &mut R,
+    dir: &WadDirEntry,
+    is_wad3: bool,
+) -> std::result::Result<WadLump, WadSkipReason> {
+    let mut lump = || -> Result<WadLump> {
+        let offset = dir.offset as usize;
         reader.seek(SeekFrom::Start(offset as u64))?;
-        if let Ok(texture) = Texture::decode(reader, offset) {
-            let name = String::from_utf8_lossy(&entry.name).to_string();
-            let name = name.split('\0').next().unwrap_or_default().to_string();
 
-            textures.insert(name, texture);
+        Ok(match dir.kind {
+            KIND_MIPTEX => WadLump::MipTexture(Texture::decode(reader, offset)?),
+            KIND_QUAKE_MIPTEX => {
+                let mut texture = Texture::decode(reader, offset)?;
+                texture.palette = [[0; 3]; 256];
+
+                WadLump::QuakeMipTexture(texture)
+            }
+            KIND_PICTURE => {
+                let width = reader.read_u32::<LittleEndian>()?;
+                let height = reader.read_u32::<LittleEndian>()?;
+                let data = read_bytes(reader, dir, 8, width as u64 * height as u64)?;
+
+                let palette = if is_wad3 {
+                    Some(read_lump_palette(reader)?)
+                } else {
+                    None
+                };
+
+                WadLump::Picture(Picture {
+                    width,
+                    height,
+                    data,
+                    palette,
+                })
+            }
+            KIND_FONT => {
+                let width = reader.read_u32::<LittleEndian>()?;
+                let height = reader.read_u32::<LittleEndian>()?;
+                let row_count = reader.read_u32::<LittleEndian>()?;
+                let row_height = reader.read_u32::<LittleEndian>()?;
+
+                let mut chars = Vec::with_capacity(256);
+                for _ in 0..256 {
+                    chars.push(FontChar {
+                        offset: reader.read_u16::<LittleEndian>()?,
+                        width: reader.read_u16::<LittleEndian>()?,
+                    });
+                }
+
+                let data = read_bytes(reader, dir, 16 + 256 * 4, width as u64 * height as u64)?;
+                let palette = read_lump_palette(reader)?;
+
+                WadLump::Font(Font {
+                    width,
+                    height,
+                    row_count,
+                    row_height,
+                    chars,
+                    data,
+                    palette,
+                })
+            }
+            KIND_PALETTE => {
+                let mut palette = Box::new([[0; 3]; 256]);
+
+                for color in palette.iter_mut() {
+                    *color = read_array_u8(reader)?;
+                }
+
+                WadLump::Palette(palette)
+            }
+            _ => return Err(Error::InvalidWadFormat),
+        })
+    };
+
+    match dir.kind {
+        KIND_MIPTEX | KIND_QUAKE_MIPTEX | KIND_PICTURE | KIND_FONT | KIND_PALETTE => {
+            lump().map_err(|e| WadSkipReason::Invalid(e.to_string()))
         }
+        _ => Err(WadSkipReason::UnknownKind),
     }
+}
+
+/// Reads `len` bytes that come `header` bytes into a lump, sizes from the file
+/// are checked against the entry's size before allocating for them
+fn read_bytes<R: Read + Seek>(
+    reader: &mut R,
+    dir: &WadDirEntry,
+    header: u64,
+    len: u64,
+) -> Result<Vec<u8>> {
+    if header + len > dir.disk_size as u64 {
+        return Err(Error::InvalidWadLump("size larger than its entry"));
+    }
+
+    let mut data = vec![0; len as usize];
+    reader.read_exact(&mut data)?;
+
+    Ok(data)
+}
 
-    Ok(Wad { textures })
+/// Palette stored after WAD3 pictures and fonts, a color count then colors
+fn read_lump_palette<R: Read + Seek>(reader: &mut R) -> Result<Box<Palette>> {
+    let count = reader.read_u16::<LittleEndian>()? as usize;
+    let mut palette = Box::new([[0; 3]; 256]);
+
+    for color in palette.iter_mut().take(count) {
+        *color = read_array_u8(reader)?;
+    }
+
+    Ok(palette)
+}
+
+#[derive(Debug)]
+pub(crate) struct Encoder<W: Write + Seek> {
+    writer: W,
+    start: u64,
+    entries: Vec<WadDirEntry>,
+}
+
+impl<W: Write + Seek> Encoder<W> {
+    pub(crate) fn new(mut writer: W) -> Result<Self> {
+        let start = writer.stream_position()?;
+
+        // Directory location is filled in by `finish`
+        Header {
+            ident: *b"WAD3",
+            num_dirs: 0,
+            dir_offset: 0,
+        }
+        .encode(&mut writer)?;
+
+        Ok(Encoder {
+            writer,
+            start,
+            entries: vec![],
+        })
+    }
+
+    pub(crate) fn add_texture(
+        &mut self,
+        name: &str,
+        width: u32,
+        height: u32,
+        rgba: &[u8],
+    ) -> Result<()> {
+        if self
+            .entries
+            .iter()
+            .any(|entry| entry.name.eq_ignore_ascii_case(name))
+        {
+            return Err(Error::Custom(format!("Duplicate WAD texture: {}", name)));
+        }
+
+        let mut lump = vec![];
+        mip_texture(name, width, height, rgba)?.encode(&mut lump)?;
+
+        let offset = self.offset()?;
+        self.writer.write_all(&lump)?;
+
+        self.entries.push(WadDirEntry {
+            name: name.to_string(),
+            kind: KIND_MIPTEX,
+            offset,
+            disk_size: lump.len() as u32,
+            size: lump.len() as u32,
+            compressed: false,
+        });
+
+        Ok(())
+    }
+
+    pub(crate) fn finish(mut self) -> Result<W> {
+        let dir_offset = self.offset()?;
+
+        for entry in self.entries.iter() {
+            let mut name = [0; MAXTEXTURENAME];
+            name[..entry.name.len()].copy_from_slice(entry.name.as_bytes());
+
+            DirEntry {
+                offset: entry.offset as i32,
+                disk_size: entry.disk_size as i32,
+                size: entry.size as i32,
+                kind: entry.kind,
+                compression: false,
+                dummy: 0,
+                name,
+            }
+            .encode(&mut self.writer)?;
+        }
+
+        let end = self.writer.stream_position()?;
+
+        self.writer.seek(SeekFrom::Start(self.start))?;
+        Header {
+            ident: *b"WAD3",
+            num_dirs: self.entries.len() as i32,
+            dir_offset: dir_offset as i32,
+        }
+        .encode(&mut self.writer)?;
+        self.writer.seek(SeekFrom::Start(end))?;
+
+        Ok(self.writer)
+    }
+
+    /// Offset from the start of the WAD, which may not be the start of `writer`
+    fn offset(&mut self) -> Result<u32> {
+        let offset = self.writer.stream_position()? - self.start;
+
+        if offset > i32::MAX as u64 {
+            return Err(Error::Custom("WAD larger than 2 GiB".to_string()));
+        }
+
+        Ok(offset as u32)
+    }
+}
+
+/// Palette index `{` textures use for transparent pixels, drawn as blue by
+/// the tools
+pub const TRANSPARENT_INDEX: u8 = 255;
+
+/// Builds a Half-Life mip texture from RGBA8 rows. The palette is quantized
+/// from the image and each mip level is box filtered from the full size one.
+/// Alpha only matters for `{` textures, where pixels under half opacity get
+/// [`TRANSPARENT_INDEX`]
+pub(crate) fn mip_texture(name: &str, width: u32, height: u32, rgba: &[u8]) -> Result<Texture> {
+    if name.is_empty() || name.len() >= MAXTEXTURENAME || !name.is_ascii() {
+        return Err(Error::Custom(format!(
+            "Texture name must be 1 to {} ASCII characters: {}",
+            MAXTEXTURENAME - 1,
+            name
+        )));
+    }
+
+    if width == 0 || height == 0 || width & 15 != 0 || height & 15 != 0 {
+        return Err(Error::Custom(format!(
+            "Texture size must be a multiple of 16: {} is {}x{}",
+            name, width, height
+        )));
+    }
+
+    if rgba.len() != width as usize * height as usize * 4 {
+        return Err(Error::Custom(format!(
+            "Texture data doesn't match its size: {}",
+            name
+        )));
+    }
+
+    let transparent = name.starts_with('{');
+    let is_opaque = |pixel: &[u8]| !transparent || pixel[3] >= 128;
+
+    let colors = rgba
+        .chunks_exact(4)
+        .filter(|pixel| is_opaque(pixel))
+        .map(|pixel| [pixel[0], pixel[1], pixel[2]]);
+
+    let mut palette = quantize::palette(colors, if transparent { 255 } else { 256 });
+    let used = palette.len();
+    palette.resize(256, [0; 3]);
+
+    if transparent {
+        palette[TRANSPARENT_INDEX as usize] = [0, 0, 255];
+    }
+
+    let mut lookup = HashMap::new();
+    let mut mips: [Vec<u8>; MIPLEVELS] = Default::default();
+
+    for (level, mip) in mips.iter_mut().enumerate() {
+        let scale = 1 << level;
+        let (mip_width, mip_height) = (width / scale, height / scale);
+
+        for y in 0..mip_height {
+            for x in 0..mip_width {
+                let mut sum = [0u32; 3];
+                let mut opaque = 0;
+
+                for sy in y * scale..(y + 1) * scale {
+                    for sx in x * scale..(x + 1) * scale {
+                        let idx = (sy * width + sx) as usize * 4;
+                        let pixel = &rgba[idx..idx + 4];
+
+                        if is_opaque(pixel) {
+                            sum[0] += pixel[0] as u32;
+                            sum[1] += pixel[1] as u32;
+                            sum[2] += pixel[2] as u32;
+                            opaque += 1;
+                        }
+                    }
+                }
+
+                // Mostly transparent blocks stay transparent in smaller mips
+                if transparent && opaque * 2 <= scale * scale {
+                    mip.push(TRANSPARENT_INDEX);
+                } else {
+                    let color = [
+                        ((sum[0] + opaque / 2) / opaque) as u8,
+                        ((sum[1] + opaque / 2) / opaque) as u8,
+                        ((sum[2] + opaque / 2) / opaque) as u8,
+                    ];
+
+                    mip.push(
+                        *lookup
+                            .entry(color)
+                            .or_insert_with(|| quantize::nearest(&palette[..used], color)),
+                    );
+                }
+            }
+        }
+    }
+
+    let mut padded = [0; MAXTEXTURENAME];
+    padded[..name.len()].copy_from_slice(name.as_bytes());
+
+    let mut colors = [[0; 3]; 256];
+    colors.copy_from_slice(&palette);
+
+    let [mip, half, quarter, eighth] = mips;
+
+    Ok(Texture {
+        name: padded,
+        width,
+        height,
+        offsets: mip_offsets(width, height),
+        mip,
+        lower_mips: [half, quarter, eighth],
+        palette: colors,
+    })
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -57,6 +636,16 @@ struct Header {
     dir_offset: i32,
 }
 
+impl ByteEncoder for Header {
+    fn encode<W: Write>(&self, writer: &mut W) -> Result<()> {
+        writer.write_all(&self.ident)?;
+        writer.write_i32::<LittleEndian>(self.num_dirs)?;
+        writer.write_i32::<LittleEndian>(self.dir_offset)?;
+
+        Ok(())
+    }
+}
+
 impl ByteDecoder for Header {
     type Output = Header;
 
@@ -80,6 +669,20 @@ struct DirEntry {
     name: [u8; MAXTEXTURENAME],
 }
 
+impl ByteEncoder for DirEntry {
+    fn encode<W: Write>(&self, writer: &mut W) -> Result<()> {
+        writer.write_i32::<LittleEndian>(self.offset)?;
+        writer.write_i32::<LittleEndian>(self.disk_size)?;
+        writer.write_i32::<LittleEndian>(self.size)?;
+        writer.write_u8(self.kind)?;
+        writer.write_u8(self.compression as u8)?;
+        writer.write_i16::<LittleEndian>(self.dummy)?;
+        writer.write_all(&self.name)?;
+
+        Ok(())
+    }
+}
+
 impl ByteDecoder for DirEntry {
     type Output = DirEntry;
 
@@ -95,3 +698,190 @@ impl ByteDecoder for DirEntry {
         })
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_declared_wads() {
+        let worldspawn = Entity {
+            properties: vec![
+                ("classname".to_string(), "worldspawn".to_string()),
+                (
+                    "wad".to_string(),
+                    "\\half-life\\valve\\halflife.wad;C:/maps/custom.wad;;decals.wad;".to_string(),
+                ),
+            ],
+        };
+
+        let mut entities = [worldspawn];
+
+        assert_eq!(
+            declared_wads(&entities),
+            vec!["halflife.wad", "custom.wad", "decals.wad"]
+        );
+        assert!(declared_wads(&[]).is_empty());
+
+        strip_declared_wads(&mut entities, &["CUSTOM.WAD", "decals.wad"]);
+        assert_eq!(
+            entities[0].get("wad"),
+            Some("\\half-life\\valve\\halflife.wad;")
+        );
+    }
+
+    #[test]
+    fn test_decode_lumps() {
+        let mut data = b"WAD3".to_vec();
+        data.extend_from_slice(&3i32.to_le_bytes());
+        data.extend_from_slice(&0i32.to_le_bytes());
+
+        // 2x1 qpic with a two color palette
+        let picture_offset = data.len() as i32;
+        data.extend_from_slice(&2u32.to_le_bytes());
+        data.extend_from_slice(&1u32.to_le_bytes());
+        data.extend_from_slice(&[1, 0]);
+        data.extend_from_slice(&2u16.to_le_bytes());
+        data.extend_from_slice(&[0, 0, 0, 255, 0, 0]);
+        let picture_size = data.len() as i32 - picture_offset;
+
+        let dir_offset = data.len() as i32;
+        data[8..12].copy_from_slice(&dir_offset.to_le_bytes());
+
+        let mut dir_entry = |name: &[u8], kind: u8, compression: u8, size: i32| {
+            data.extend_from_slice(&picture_offset.to_le_bytes());
+            data.extend_from_slice(&size.to_le_bytes());
+            data.extend_from_slice(&size.to_le_bytes());
+            data.extend_from_slice(&[kind, compression, 0, 0]);
+
+            let mut padded = [0; MAXTEXTURENAME];
+            padded[..name.len()].copy_from_slice(name);
+            data.extend_from_slice(&padded);
+        };
+        dir_entry(b"crosshairs", KIND_PICTURE, 0, picture_size);
+        dir_entry(b"sound", 0x45, 0, picture_size);
+        dir_entry(b"packed", KIND_PICTURE, 1, picture_size);
+
+        let data_copy = data.clone();
+        let wad = decode(&mut std::io::Cursor::new(data)).unwrap();
+
+        assert!(wad.textures.is_empty());
+        assert_eq!(wad.entries.len(), 3);
+
+        match &wad.entries[0].lump {
+            Ok(WadLump::Picture(picture)) => {
+                assert_eq!((picture.width, picture.height), (2, 1));
+                assert_eq!(picture.data, vec![1, 0]);
+                assert_eq!(picture.palette.as_ref().unwrap()[1], [255, 0, 0]);
+            }
+            lump => panic!("expected a picture, got {:?}", lump),
+        }
+
+        assert_eq!(wad.entries[1].dir.name, "sound");
+        assert_eq!(
+            wad.entries[1].lump.as_ref().err(),
+            Some(&WadSkipReason::UnknownKind)
+        );
+        assert_eq!(
+            wad.entries[2].lump.as_ref().err(),
+            Some(&WadSkipReason::Compressed)
+        );
+
+        let mut index = WadIndex::new(std::io::Cursor::new(data_copy)).unwrap();
+
+        assert_eq!(index.entries().len(), 3);
+        assert_eq!(index.find("CrossHairs").unwrap().kind, KIND_PICTURE);
+        assert!(matches!(
+            index.lump("CROSSHAIRS"),
+            Some(Ok(WadLump::Picture(_)))
+        ));
+        assert!(index.texture("crosshairs").is_none());
+        assert!(index.lump("missing").is_none());
+    }
+
+    #[test]
+    fn test_decode_oversized_lumps() {
+        let mut data = b"WAD3".to_vec();
+        data.extend_from_slice(&2i32.to_le_bytes());
+        data.extend_from_slice(&0i32.to_le_bytes());
+
+        // Claims 65535x65535 with only a few bytes behind it
+        let lump_offset = data.len() as i32;
+        data.extend_from_slice(&0xffffu32.to_le_bytes());
+        data.extend_from_slice(&0xffffu32.to_le_bytes());
+        data.extend_from_slice(&[0; 16]);
+        let lump_size = data.len() as i32 - lump_offset;
+
+        let dir_offset = data.len() as i32;
+        data[8..12].copy_from_slice(&dir_offset.to_le_bytes());
+
+        for (name, kind) in [(&b"huge"[..], KIND_PICTURE), (&b"font"[..], KIND_FONT)] {
+            data.extend_from_slice(&lump_offset.to_le_bytes());
+            data.extend_from_slice(&lump_size.to_le_bytes());
+            data.extend_from_slice(&lump_size.to_le_bytes());
+            data.extend_from_slice(&[kind, 0, 0, 0]);
+
+            let mut padded = [0; MAXTEXTURENAME];
+            padded[..name.len()].copy_from_slice(name);
+            data.extend_from_slice(&padded);
+        }
+
+        let wad = decode(&mut std::io::Cursor::new(data)).unwrap();
+
+        assert_eq!(wad.entries.len(), 2);
+        assert!(wad
+            .entries
+            .iter()
+            .all(|entry| matches!(entry.lump, Err(WadSkipReason::Invalid(_)))));
+    }
+
+    #[test]
+    fn test_encode_textures() {
+        // Left half red, right half green, with a transparent top row
+        let mut rgba = vec![];
+        for y in 0..16 {
+            for x in 0..32 {
+                let color = if x < 16 { [255, 0, 0] } else { [0, 255, 0] };
+                let alpha = if y == 0 { 0 } else { 255 };
+
+                rgba.extend_from_slice(&color);
+                rgba.push(alpha);
+            }
+        }
+
+        let mut encoder = Encoder::new(std::io::Cursor::new(vec![])).unwrap();
+        encoder.add_texture("{fence", 32, 16, &rgba).unwrap();
+        encoder.add_texture("wall", 32, 16, &rgba).unwrap();
+        assert!(encoder.add_texture("WALL", 32, 16, &rgba).is_err());
+        assert!(encoder.add_texture("odd", 20, 16, &rgba).is_err());
+        assert!(encoder
+            .add_texture("a_very_long_name", 32, 16, &rgba)
+            .is_err());
+
+        let mut reader = encoder.finish().unwrap();
+        reader.set_position(0);
+
+        let mut index = WadIndex::new(reader).unwrap();
+        assert_eq!(index.entries().len(), 2);
+
+        let fence = index.texture("{FENCE").unwrap();
+        assert_eq!(fence.name_string(), "{fence");
+        assert_eq!((fence.width, fence.height), (32, 16));
+        assert_eq!(fence.offsets, [40, 40 + 512, 40 + 640, 40 + 672]);
+        assert_eq!(fence.palette[TRANSPARENT_INDEX as usize], [0, 0, 255]);
+        assert_eq!(fence.mip[0], TRANSPARENT_INDEX);
+        assert_eq!(fence.palette[fence.mip[32] as usize], [255, 0, 0]);
+        assert_eq!(fence.palette[fence.mip[63] as usize], [0, 255, 0]);
+
+        let wall = index.texture("wall").unwrap();
+        assert_eq!(wall.palette[wall.mip[0] as usize], [255, 0, 0]);
+        assert_eq!(wall.palette[wall.mip[31] as usize], [0, 255, 0]);
+
+        // Smallest mip of the fence is 4x2, its blocks are mostly opaque
+        let smallest = index.find("{fence").unwrap().offset as usize + 40 + 672;
+        let data = index.into_inner().into_inner();
+        assert!(data[smallest..smallest + 8]
+            .iter()
+            .all(|&idx| idx != TRANSPARENT_INDEX));
+    }
+}
@@ -1,11 +1,12 @@
 use std::collections::HashMap;
 use std::fs;
 use std::io::BufReader;
-use std::path::Path;
-use std::sync::{Arc, RwLock};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
 
 use anyhow::format_err;
 use decoder::format::gold_src_30::Texture;
+use decoder::wad::{declared_wads, WadIndex};
 use decoder::{BspDecoder, WadDecoder};
 use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
 use rg3d::core::algebra::{Vector2, Vector3};
@@ -83,6 +84,8 @@ fn load_map<P: AsRef<Path>>(path: P, wad_manager: &WadManager) -> Result<Scene,
         .get(0)
         .ok_or_else(|| format_err!("No worldspawn model"))?;
 
+    let wads = wad_manager.resolve(&declared_wads(&bsp.entities));
+
     let mut textures = HashMap::new();
     let mut surfaces = vec![];
 
@@ -92,12 +95,19 @@ fn load_map<P: AsRef<Path>>(path: P, wad_manager: &WadManager) -> Result<Scene,
         let name = name.split('\0').next().unwrap_or_default().to_string();
 
         // WAD textures are 0
+        let wad_texture;
         if let Some(bsp_texture) = if texture.offsets[0] > 0 {
             Some(texture)
         } else {
-            wad_manager.textures.get(&name)
+            wad_texture = wads.iter().find_map(|wad| {
+                wad.lock()
+                    .unwrap()
+                    .texture(&name)
+                    .filter(|texture| texture.width > 0 && texture.height > 0)
+            });
+            wad_texture.as_ref()
         } {
-            if let Some(texture) = parse_texture(&bsp_texture) {
+            if let Some(texture) = parse_texture(bsp_texture) {
                 textures.insert(name, texture);
             }
         }
@@ -252,48 +262,85 @@ fn load_map<P: AsRef<Path>>(path: P, wad_manager: &WadManager) -> Result<Scene,
     Ok(scene)
 }
 
+/// Loads the WADs maps declare from the search directories, once each
 #[derive(Debug, Default)]
 pub struct WadManager {
-    textures: HashMap<String, Texture>,
+    search_dirs: Vec<PathBuf>,
+    wads: RwLock<HashMap<String, Option<SharedWad>>>,
 }
 
+/// Textures are decoded from the open file when a map uses them
+type SharedWad = Arc<Mutex<WadIndex<BufReader<fs::File>>>>;
+
 impl WadManager {
-    pub fn new<P: AsRef<Path>>(directory: P) -> Self {
-        let mut manager = WadManager::default();
+    pub fn new<P: AsRef<Path>>(search_dirs: &[P]) -> Self {
+        WadManager {
+            search_dirs: search_dirs
+                .iter()
+                .map(|p| p.as_ref().to_path_buf())
+                .collect(),
+            ..Default::default()
+        }
+    }
 
-        let mut wads = vec![];
+    /// The declared WADs that could be found, in the map's search order
+    fn resolve(&self, names: &[String]) -> Vec<SharedWad> {
+        names.iter().filter_map(|name| self.wad(name)).collect()
+    }
 
-        if let Ok(dir) = fs::read_dir(directory) {
-            for entry in dir.flatten() {
-                let path = entry.path();
+    fn wad(&self, name: &str) -> Option<SharedWad> {
+        if let Some(wad) = self.wads.read().unwrap().get(name) {
+            return wad.clone();
+        }
 
-                if let Ok(name) = entry.file_name().into_string() {
-                    if name.ends_with(".wad") {
-                        if let Ok(file) = fs::File::open(path) {
-                            let reader = BufReader::new(file);
+        let path = self.search_dirs.iter().find_map(|dir| find_file(dir, name));
 
-                            match WadDecoder::from_reader(reader).decode() {
-                                Ok(wad) => wads.push(wad),
-                                Err(e) => {
-                                    dbg!(&e);
-                                }
-                            }
-                        }
+        let wad = match path {
+            Some(path) => {
+                let decoded = fs::File::open(&path)
+                    .map_err(decoder::Error::from)
+                    .and_then(|file| WadDecoder::from_reader(BufReader::new(file)).index());
+
+                match decoded {
+                    Ok(wad) => Some(Arc::new(Mutex::new(wad))),
+                    Err(e) => {
+                        println!("Can't decode WAD {}: {}", path.display(), e);
+                        None
                     }
                 }
             }
-        }
-
-        for wad in wads.into_iter() {
-            for (name, texture) in wad.textures.into_iter() {
-                if texture.width > 0 && texture.height > 0 {
-                    manager.textures.insert(name, texture);
-                }
+            None => {
+                println!("WAD missing: {}, searched {:?}", name, self.search_dirs);
+                None
             }
-        }
+        };
+
+        self.wads
+            .write()
+            .unwrap()
+            .insert(name.to_string(), wad.clone());
+
+        wad
+    }
+}
+
+/// Path of `name` in `dir`, the file name can differ in case
+fn find_file(dir: &Path, name: &str) -> Option<PathBuf> {
+    let path = dir.join(name);
 
-        manager
+    if path.is_file() {
+        return Some(path);
     }
+
+    fs::read_dir(dir)
+        .ok()?
+        .flatten()
+        .map(|entry| entry.path())
+        .find(|path| {
+            path.file_name()
+                .map(|n| n.to_string_lossy().eq_ignore_ascii_case(name))
+                .unwrap_or_default()
+        })
 }
 
 fn triangulate(verts: &[StaticVertex]) -> Vec<TriangleDefinition> {
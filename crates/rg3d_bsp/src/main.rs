@@ -44,7 +44,7 @@ impl GameState for Game {
 
         let debug_text = create_ui(&mut engine.user_interface.build_ctx());
 
-        let wad_manager = loader::WadManager::new("assets/wads");
+        let wad_manager = loader::WadManager::new(&["assets/wads"]);
 
         let maps = loader::load_maps("assets/maps", &wad_manager);
 
@@ -1,13 +1,19 @@
-use std::fs;
+use std::sync::Arc;
+use std::{env, iter};
 
+use bevy::asset::AssetPlugin;
 use bevy::pbr::AmbientLight;
 use bevy::prelude::*;
 use bevy::render::camera::PerspectiveProjection;
 use bevy::render::wireframe::WireframePlugin;
 use bevy::scene::InstanceId;
 use bevy::wgpu::{WgpuFeature, WgpuFeatures, WgpuOptions};
-use bevy_bsp::BspPlugin;
+use bevy_bsp::{
+    BspBrushModel, BspConfig, BspMover, BspPlugin, BspTriggerActivator, BspUseEvent, BspVfs,
+    BspVfsPlugin, SKY_FAR_PLANE,
+};
 use bevy_fly_camera::{FlyCamera, FlyCameraPlugin};
+use decoder::vfs::Vfs;
 
 //use bevy_fly_camera::{FlyCamera, FlyCameraPlugin};
 use crate::plugins::ui::UiPlugin;
@@ -15,6 +21,15 @@ use crate::plugins::ui::UiPlugin;
 mod plugins;
 
 fn main() {
+    // Game and mod directories to load from, highest priority first, e.g.
+    // `bsp-rs ~/hl/cstrike ~/hl/valve`. The asset folder comes last
+    let mut vfs = Vfs::new();
+    for dir in env::args().skip(1).chain(iter::once("assets".to_string())) {
+        if let Err(e) = vfs.mount_game_dir(&dir) {
+            eprintln!("Can't mount {}: {}", dir, e);
+        }
+    }
+
     App::build()
         .insert_resource(WgpuOptions {
             features: WgpuFeatures {
@@ -35,13 +50,21 @@ fn main() {
         })
         .insert_resource(AppState::default())
         .add_event::<Event>()
-        .add_plugins(DefaultPlugins)
+        .insert_resource(BspConfig {
+            // Game directories keep their WADs at the root
+            wad_dirs: vec!["".to_string(), "wads".to_string()],
+            ..Default::default()
+        })
+        .add_plugins_with(DefaultPlugins, |group| {
+            group.add_before::<AssetPlugin, _>(BspVfsPlugin { vfs: Arc::new(vfs) })
+        })
         .add_plugin(WireframePlugin)
         .add_plugin(UiPlugin)
         .add_plugin(FlyCameraPlugin)
         .add_plugin(BspPlugin)
         .add_system(cursor_grab_system.system())
         .add_system(change_map_system.system())
+        .add_system(use_system.system())
         .add_startup_system(setup.system())
         .run();
 }
@@ -60,25 +83,34 @@ struct Map {
     instance_id: Option<InstanceId>,
 }
 
-fn setup(mut commands: Commands, mut state: ResMut<AppState>, mut events: EventWriter<Event>) {
+fn setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    vfs: Res<BspVfs>,
+    mut state: ResMut<AppState>,
+    mut events: EventWriter<Event>,
+) {
+    // Reload WAD textures when they change on disk
+    if let Err(e) = asset_server.watch_for_changes() {
+        warn!("Can't watch assets for changes: {:?}", e);
+    }
+
     let mut maps = vec![];
 
-    if let Ok(dir) = fs::read_dir("assets/maps") {
-        for entry in dir.flatten() {
-            if let Ok(name) = entry.file_name().into_string() {
-                if name.ends_with(".bsp") {
-                    //let scene_handle = asset_server.load(format!("maps/{}#Map", &name).as_str());
+    for path in vfs.0.read_dir("maps") {
+        let name = path.trim_start_matches("maps/").to_string();
 
-                    maps.push(Map {
-                        name,
-                        scene_handle: None,
-                        instance_id: None,
-                    });
-                }
-            }
+        if name.to_lowercase().ends_with(".bsp") {
+            //let scene_handle = asset_server.load(format!("maps/{}#Map", &name).as_str());
+
+            maps.push(Map {
+                name,
+                scene_handle: None,
+                instance_id: None,
+            });
         }
     }
-    maps.sort_by_key(|m| m.name.clone());
+    maps.sort_by_key(|m| m.name.to_lowercase());
 
     state.maps = maps;
 
@@ -90,7 +122,7 @@ fn setup(mut commands: Commands, mut state: ResMut<AppState>, mut events: EventW
     let perspective_projection = PerspectiveProjection {
         fov: 90.0,
         near: 0.1,
-        far: 6000.0,
+        far: SKY_FAR_PLANE,
         ..Default::default()
     };
 
@@ -107,7 +139,8 @@ fn setup(mut commands: Commands, mut state: ResMut<AppState>, mut events: EventW
             sensitivity: 10.0,
             enabled: false,
             ..Default::default()
-        });
+        })
+        .insert(BspTriggerActivator);
 
     // UI camera
     commands.spawn_bundle(UiCameraBundle::default());
@@ -140,6 +173,41 @@ fn cursor_grab_system(
     }
 }
 
+/// `E` uses the mover in front of the camera, `U` uses every mover
+fn use_system(
+    key: Res<Input<KeyCode>>,
+    cameras: Query<&GlobalTransform, With<FlyCamera>>,
+    movers: Query<(Entity, &BspBrushModel, &GlobalTransform), With<BspMover>>,
+    mut events: EventWriter<BspUseEvent>,
+) {
+    if key.just_pressed(KeyCode::U) {
+        for (entity, _, _) in movers.iter() {
+            events.send(BspUseEvent(entity));
+        }
+    }
+
+    if key.just_pressed(KeyCode::E) {
+        for camera in cameras.iter() {
+            let direction = -camera.local_z();
+
+            let closest = movers
+                .iter()
+                .filter_map(|(entity, model, transform)| {
+                    let origin = camera.translation - transform.translation;
+
+                    model
+                        .ray_intersection(origin, direction)
+                        .map(|distance| (entity, distance))
+                })
+                .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal));
+
+            if let Some((entity, _)) = closest {
+                events.send(BspUseEvent(entity));
+            }
+        }
+    }
+}
+
 enum Event {
     LoadMap(usize),
 }
@@ -167,7 +235,9 @@ fn change_map_system(
 
                     map.scene_handle = Some(scene_handle.clone());
 
-                    let scene_entity = commands.spawn().id();
+                    let scene_entity = commands
+                        .spawn_bundle((Transform::identity(), GlobalTransform::identity()))
+                        .id();
                     let instance_id = scene_spawner.spawn_as_child(scene_handle, scene_entity);
 
                     map.instance_id = Some(instance_id);